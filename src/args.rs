@@ -1,69 +1,110 @@
 use {
     std::{
         collections::{
-            BTreeSet,
-            HashSet
+            BTreeMap,
+            HashSet,
+            btree_map
         },
         env,
-        fs::File,
+        fmt,
+        fs::{
+            self,
+            File
+        },
         io::{
             self,
-            BufReader,
             Cursor,
-            prelude::*,
             stdout
         },
-        path::PathBuf,
-        str::FromStr
+        path::{
+            Path,
+            PathBuf
+        },
+        str::FromStr,
+        time::Duration
     },
     css_color_parser::Color,
     smart_default::SmartDefault,
+    url::Url,
     crate::{
         art::ArtHandler,
+        cache::CacheCommand,
+        decklist,
+        manifest::ImageManifest,
         mse::DataFile,
+        overrides::Overrides,
         util::{
             Error,
+            IntoResultExt as _,
             IoResultExt as _
         }
     }
 };
+#[cfg(not(unix))] use directories::ProjectDirs;
 #[cfg(not(windows))] use {
     std::io::stdin,
     termion::is_tty
 };
 
-//TODO !tappedout command
-const COMMANDS: [(&str, usize, fn(&mut ArgsRegular, Vec<String>) -> Result<(), Error>); 1] = [
-    ("all", 0, command_all)
+const COMMANDS: [(&str, usize, fn(&mut ArgsRegular, Vec<String>) -> Result<(), Error>); 2] = [
+    ("all", 0, command_all),
+    ("tappedout", 1, command_tappedout)
 ];
 
 //TODO add remaining flags/options from readme
-const FLAGS: [(&str, Option<char>, fn(&mut ArgsRegular) -> Result<(), Error>); 11] = [
+const FLAGS: [(&str, Option<char>, fn(&mut ArgsRegular) -> Result<(), Error>); 21] = [
     ("auto-card-numbers", None, auto_card_numbers),
+    ("copies", None, copies),
+    ("crop-images", None, crop_images),
     ("holofoil-stamps", None, holofoil_stamps),
     ("include-schemes", None, include_schemes_on),
     ("include-vanguards", None, include_vanguards_on),
+    ("no-auto-card-numbers", None, no_auto_card_numbers),
+    ("no-copies", None, no_copies),
+    ("no-crop-images", None, no_crop_images),
+    ("no-holofoil-stamps", None, no_holofoil_stamps),
     ("no-images", None, no_images),
     ("no-include-schemes", None, include_schemes_off),
     ("no-include-vanguards", None, include_vanguards_off),
     ("no-lore-seeker-images", None, no_lore_seeker_images),
+    ("no-offline", None, no_offline),
     ("no-scryfall-images", None, no_scryfall_images),
+    ("no-strict", None, no_strict),
+    ("no-verbose", None, no_verbose),
     ("offline", None, offline),
+    ("strict", None, strict),
     ("verbose", Some('v'), verbose)
 ];
 
-const OPTIONS: [(&str, Option<char>, fn(&mut ArgsRegular, &str) -> Result<(), Error>); 12] = [
+const OPTIONS: [(&str, Option<char>, fn(&mut ArgsRegular, &str) -> Result<(), Error>); 29] = [
     ("border", Some('b'), border),
     ("copyright", None, copyright),
     ("db", None, database),
+    ("find-cards", None, find_cards),
+    ("image-jpeg-quality", None, image_jpeg_quality),
+    ("image-manifest", None, image_manifest),
+    ("image-max-size", None, image_max_size),
+    ("image-sources", None, image_sources),
+    ("image-url-template", None, image_url_template),
     ("images", None, images),
     ("input", Some('i'), input),
     ("lore-seeker-hostname", None, lore_seeker_hostname),
     ("lore-seeker-images", None, lore_seeker_images),
     ("output", Some('o'), output),
+    ("overrides", None, overrides),
+    ("query-backend", None, query_backend),
     ("schemes-output", None, schemes_output),
+    ("scryfall-api-url", None, scryfall_api_url),
+    ("scryfall-image-variant", None, scryfall_image_variant),
     ("scryfall-images", None, scryfall_images),
     ("set-code", None, set_code),
+    ("set-description", None, set_description),
+    ("set-language", None, set_language),
+    ("set-symbol", None, set_symbol),
+    ("set-title", None, set_title),
+    ("shared-face-images", None, shared_face_images),
+    ("styling", None, styling),
+    ("tappedout-url", None, tappedout_url),
     ("vanguards-output", None, vanguards_output)
 ];
 
@@ -102,17 +143,150 @@ impl Output {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtSource {
+    Local,
+    Scryfall,
+    LoreSeeker,
+    UrlTemplate
+}
+
+impl FromStr for ArtSource {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ArtSource, Error> {
+        match s {
+            "local" => Ok(ArtSource::Local),
+            "scryfall" => Ok(ArtSource::Scryfall),
+            "lore-seeker" => Ok(ArtSource::LoreSeeker),
+            "url-template" => Ok(ArtSource::UrlTemplate),
+            _ => Err(Error::Args(format!("unknown image source: {}", s)))
+        }
+    }
+}
+
+#[derive(Debug, SmartDefault, Clone, Copy, PartialEq, Eq)]
+pub enum ScryfallImageVariant {
+    #[default]
+    ArtCrop,
+    Large,
+    Png,
+    BorderCrop
+}
+
+impl ScryfallImageVariant {
+    pub fn api_name(&self) -> &'static str {
+        match self {
+            ScryfallImageVariant::ArtCrop => "art_crop",
+            ScryfallImageVariant::Large => "large",
+            ScryfallImageVariant::Png => "png",
+            ScryfallImageVariant::BorderCrop => "border_crop"
+        }
+    }
+
+    pub fn file_ext(&self) -> &'static str {
+        match self {
+            ScryfallImageVariant::Png => "png",
+            ScryfallImageVariant::ArtCrop | ScryfallImageVariant::Large | ScryfallImageVariant::BorderCrop => "jpg"
+        }
+    }
+}
+
+impl FromStr for ScryfallImageVariant {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ScryfallImageVariant, Error> {
+        match s {
+            "art-crop" | "art_crop" => Ok(ScryfallImageVariant::ArtCrop),
+            "large" => Ok(ScryfallImageVariant::Large),
+            "png" => Ok(ScryfallImageVariant::Png),
+            "border-crop" | "border_crop" => Ok(ScryfallImageVariant::BorderCrop),
+            _ => Err(Error::Args(format!("unknown Scryfall image variant: {}", s)))
+        }
+    }
+}
+
+#[derive(Debug, SmartDefault, Clone, Copy, PartialEq, Eq)]
+pub enum SharedFaceImages {
+    #[default]
+    All,
+    Primary,
+    None
+}
+
+impl FromStr for SharedFaceImages {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SharedFaceImages, Error> {
+        match s {
+            "all" => Ok(SharedFaceImages::All),
+            "primary" => Ok(SharedFaceImages::Primary),
+            "none" => Ok(SharedFaceImages::None),
+            _ => Err(Error::Args(format!("unknown shared face image strategy: {}", s)))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryBackend {
+    FindCards,
+    Local,
+    LoreSeeker,
+    Scryfall
+}
+
+impl FromStr for QueryBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<QueryBackend, Error> {
+        match s {
+            "find-cards" | "find_cards" => Ok(QueryBackend::FindCards),
+            "local" => Ok(QueryBackend::Local),
+            "lore-seeker" => Ok(QueryBackend::LoreSeeker),
+            "scryfall" => Ok(QueryBackend::Scryfall),
+            _ => Err(Error::Args(format!("unknown query backend: {}", s)))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Printing {
+    pub set_code: String,
+    pub collector_number: Option<String>
+}
+
+impl fmt::Display for Printing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({})", self.set_code)?;
+        if let Some(ref collector_number) = self.collector_number {
+            write!(f, " {}", collector_number)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, SmartDefault, Clone)]
 pub struct ArgsRegular {
     pub all_command: bool,
     pub auto_card_numbers: bool,
     #[default(Color { r: 222, g: 127, b: 50, a: 1.0 })]
     pub border_color: Color,
-    pub cards: BTreeSet<String>,
+    pub cards: BTreeMap<String, usize>,
+    pub copies: bool,
     #[default = "NOT FOR SALE"]
     pub copyright: String,
+    pub crop_images: bool,
     pub database: Option<PathBuf>,
+    pub decklists: Vec<String>,
+    pub find_cards: Option<PathBuf>,
     pub holofoil_stamps: bool,
+    pub image_inputs: Vec<PathBuf>,
+    pub image_jpeg_quality: Option<u8>,
+    pub image_manifest: ImageManifest,
+    pub image_max_size: Option<u32>,
+    #[default(vec![ArtSource::Local, ArtSource::UrlTemplate, ArtSource::Scryfall, ArtSource::LoreSeeker])]
+    pub image_sources: Vec<ArtSource>,
+    pub image_url_template: Option<String>,
     pub images: Option<PathBuf>,
     include_schemes: Option<bool>,
     include_vanguards: Option<bool>,
@@ -123,11 +297,29 @@ pub struct ArgsRegular {
     no_scryfall_images: bool,
     pub offline: bool,
     pub output: Output,
+    pub overrides: Overrides,
+    pub printings: BTreeMap<String, Printing>,
     pub queries: HashSet<String>,
+    /// If `None`, the query backend is chosen based on `--find-cards` and `--offline`.
+    pub query_backend: Option<QueryBackend>,
     pub schemes_output: Option<Output>,
+    #[default(Url::parse("https://api.scryfall.com/").expect("failed to parse Scryfall API URL"))]
+    pub scryfall_api_url: Url,
+    pub scryfall_image_variant: ScryfallImageVariant,
     pub scryfall_images: Option<PathBuf>,
     #[default = "PROXY"]
     pub set_code: String,
+    pub set_description: Option<String>,
+    #[default = "EN"]
+    pub set_language: String,
+    pub set_symbol: Option<PathBuf>,
+    #[default = "MTG JSON card import"]
+    pub set_title: String,
+    pub shared_face_images: SharedFaceImages,
+    pub strict: bool,
+    pub styling: BTreeMap<String, BTreeMap<String, String>>,
+    #[default(Url::parse("https://tappedout.net/").expect("failed to parse tappedout URL"))]
+    pub tappedout_url: Url,
     pub vanguards_output: Option<Output>,
     pub verbose: bool
 }
@@ -193,11 +385,117 @@ impl ArgsRegular {
             self.queries.insert(line[1..].to_string());
             Ok(())
         } else {
-            self.cards.insert(line.into());
+            self.add_card_line(line)
+        }
+    }
+
+    fn add_card_line(&mut self, line: &str) -> Result<(), Error> {
+        let (quantity, card_name, printing) = decklist::card_line(line)?;
+        if let Some(printing) = printing {
+            match self.printings.entry(card_name.to_owned()) {
+                btree_map::Entry::Occupied(entry) => if *entry.get() != printing {
+                    return Err(Error::Args(format!("conflicting printings selected for {}: {} and {}", card_name, entry.get(), printing)));
+                },
+                btree_map::Entry::Vacant(entry) => { entry.insert(printing); }
+            }
+        }
+        self.add_card(card_name.to_owned(), quantity);
+        Ok(())
+    }
+
+    fn add_card(&mut self, card_name: String, quantity: usize) {
+        *self.cards.entry(card_name).or_default() += quantity;
+    }
+
+    /// The returned arguments have the same options as these, but only the cards, queries, decklists, and image directories from the file.
+    pub(crate) fn card_list(&self, path: &Path) -> Result<ArgsRegular, Error> {
+        let mut list_args = ArgsRegular {
+            all_command: false,
+            cards: BTreeMap::default(),
+            decklists: Vec::default(),
+            image_inputs: Vec::default(),
+            printings: BTreeMap::default(),
+            queries: HashSet::default(),
+            ..self.clone()
+        };
+        input(&mut list_args, &path.to_string_lossy())?;
+        Ok(list_args)
+    }
+
+    pub fn load_config(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let config = toml::from_str::<toml::value::Table>(&fs::read_to_string(path).at(path)?)?;
+        for (key, value) in config {
+            self.handle_config_entry(&key, value).annotate(format!("in config file {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn handle_config_entry(&mut self, key: &str, value: toml::Value) -> Result<(), Error> {
+        match value {
+            toml::Value::Boolean(enabled) => self.set_flag(key, enabled),
+            toml::Value::Array(values) => {
+                for value in values {
+                    self.handle_config_entry(key, value)?;
+                }
+                Ok(())
+            }
+            toml::Value::Integer(value) => self.set_option(key, &value.to_string()),
+            toml::Value::String(value) => self.set_option(key, &value),
+            _ => Err(Error::Args(format!("unsupported value type for option: {}", key)))
+        }
+    }
+
+    pub fn load_env(&mut self) -> Result<(), Error> {
+        for &(long, _, _) in &FLAGS {
+            if let Some(value) = env_var(long)? {
+                match &value.to_lowercase()[..] {
+                    "1" | "true" | "yes" | "on" => self.set_flag(long, true)?,
+                    "0" | "false" | "no" | "off" => self.set_flag(long, false)?,
+                    _ => { return Err(Error::Args(format!("invalid value for environment variable {}: {:?}", env_var_name(long), value))); }
+                }
+            }
+        }
+        for &(long, _, handler) in &OPTIONS {
+            if let Some(value) = env_var(long)? {
+                handler(self, &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Enables the given flag, or disables it by applying its counterpart, e.g. `include-schemes` for `no-include-schemes`.
+    fn set_flag(&mut self, name: &str, enabled: bool) -> Result<(), Error> {
+        if !FLAGS.iter().any(|(long, _, _)| *long == name) { return Err(Error::Args(format!("unknown flag: {}", name))); }
+        let flag_name = if enabled {
+            name.to_owned()
+        } else if name.starts_with("no-") && FLAGS.iter().any(|(long, _, _)| *long == &name["no-".len()..]) {
+            name["no-".len()..].to_owned()
+        } else {
+            format!("no-{}", name)
+        };
+        if let Some((_, _, handler)) = FLAGS.iter().find(|(long, _, _)| *long == flag_name) {
+            handler(self)
+        } else {
+            // the counterparts of these flags are options which take a path
+            match name {
+                "no-images" => { self.no_images = false; }
+                "no-lore-seeker-images" => { self.no_lore_seeker_images = false; }
+                "no-scryfall-images" => { self.no_scryfall_images = false; }
+                _ => { return Err(Error::Args(format!("flag can't be disabled: {}", name))); }
+            }
             Ok(())
         }
     }
 
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        if let Some((_, _, handler)) = OPTIONS.iter().find(|(long, _, _)| *long == name) {
+            handler(self, value)
+        } else {
+            Err(Error::Args(format!("unknown option: {}", name)))
+        }
+    }
+
     pub fn include_schemes(&self) -> bool {
         self.include_schemes.unwrap_or(self.schemes_output.is_none())
     }
@@ -217,6 +515,8 @@ impl ArgsRegular {
 
 pub enum Args {
     Regular(ArgsRegular),
+    Cache(ArgsRegular, CacheCommand),
+    ImageManifest(ArgsRegular),
     Help,
     Update,
     Version
@@ -230,14 +530,48 @@ enum HandleShortArgResult {
 
 impl Args {
     pub fn new() -> Result<Args, Error> {
-        let mut raw_args = env::args().skip(1);
+        let raw_args = env::args().skip(1).collect::<Vec<_>>();
         let mut args = ArgsRegular::default();
+        // config files are loaded before the remaining arguments are parsed so those take precedence
+        let mut config_paths = None::<Vec<PathBuf>>;
+        let mut config_args = raw_args.iter();
+        while let Some(arg) = config_args.next() {
+            if arg == "--no-config" {
+                config_paths.get_or_insert_with(Vec::default);
+            } else if arg == "--config" {
+                let config_path = config_args.next().ok_or(Error::Args(format!("missing value for option: --config")))?;
+                config_paths.get_or_insert_with(Vec::default).push(config_path.into());
+            } else if arg.starts_with("--config=") {
+                config_paths.get_or_insert_with(Vec::default).push(arg["--config=".len()..].into());
+            }
+        }
+        if let Some(config_paths) = config_paths {
+            for config_path in config_paths {
+                args.load_config(config_path)?;
+            }
+        } else {
+            for config_path in default_config_paths() {
+                if config_path.exists() {
+                    args.load_config(config_path)?;
+                }
+            }
+        }
+        args.load_env()?;
+        if raw_args.first().map_or(false, |arg| arg == "cache") {
+            return Args::cache_command(raw_args.into_iter().skip(1), args);
+        }
+        let image_manifest_command = raw_args.first().map_or(false, |arg| arg == "image-manifest");
+        let mut raw_args = raw_args.into_iter().skip(if image_manifest_command { 1 } else { 0 });
         while let Some(arg) = raw_args.next() {
             if arg.starts_with('-') {
                 // no stdin support since pos args aren't paths/files
                 if arg.starts_with("--") {
                     if Args::handle_long_arg(&arg, &mut raw_args, &mut args)? {
                         // handled
+                    } else if arg == "--config" {
+                        raw_args.next(); // config files have already been loaded
+                    } else if arg == "--no-config" || arg.starts_with("--config=") {
+                        // config files have already been loaded
                     } else if arg == "--help" {
                         return Ok(Args::Help);
                     } else if arg == "--update" {
@@ -278,7 +612,7 @@ impl Args {
             } else if arg.starts_with('=') {
                 args.queries.insert(arg[1..].to_string());
             } else {
-                args.cards.insert(arg);
+                args.add_card_line(&arg)?;
             }
         }
         #[cfg(not(windows))] { //TODO enable for Windows when https://gitlab.redox-os.org/redox-os/termion/issues/167 is fixed
@@ -292,7 +626,52 @@ impl Args {
                 }
             }
         }
-        Ok(Args::Regular(args))
+        Ok(if image_manifest_command { Args::ImageManifest(args) } else { Args::Regular(args) })
+    }
+
+    fn cache_command(mut raw_args: impl Iterator<Item = String>, mut args: ArgsRegular) -> Result<Args, Error> {
+        let mut subcommand = None;
+        let mut older_than = None;
+        let mut keep = None;
+        while let Some(arg) = raw_args.next() {
+            if arg.starts_with("--older-than=") {
+                let days = arg["--older-than=".len()..].trim_end_matches('d').parse::<u64>().map_err(|_| Error::Args(format!("invalid number of days: {}", arg)))?;
+                older_than = Some(Duration::from_secs(days.checked_mul(24 * 60 * 60).ok_or_else(|| Error::Args(format!("number of days too large: {}", arg)))?));
+            } else if arg.starts_with("--keep=") {
+                keep = Some(PathBuf::from(&arg["--keep=".len()..]));
+            } else if arg.starts_with("--") {
+                if Args::handle_long_arg(&arg, &mut raw_args, &mut args)? {
+                    // handled
+                } else if arg == "--config" {
+                    raw_args.next(); // config files have already been loaded
+                } else if arg == "--no-config" || arg.starts_with("--config=") {
+                    // config files have already been loaded
+                } else {
+                    return Err(Error::Args(format!("unknown option: {}", arg)));
+                }
+            } else if subcommand.is_none() {
+                subcommand = Some(arg);
+            } else {
+                return Err(Error::Args(format!("unexpected argument to msegen cache: {}", arg)));
+            }
+        }
+        let command = match subcommand.as_deref() {
+            Some("list") => CacheCommand::List,
+            Some("size") => CacheCommand::Size,
+            Some("prune") => if older_than.is_none() && keep.is_none() {
+                return Err(Error::Args(format!("msegen cache prune requires --older-than or --keep")));
+            } else {
+                CacheCommand::Prune { older_than, keep }
+            },
+            Some("verify") => CacheCommand::Verify,
+            Some("clear") => CacheCommand::Clear,
+            Some(subcommand) => { return Err(Error::Args(format!("unknown cache command: {}", subcommand))); }
+            None => { return Err(Error::Args(format!("missing cache command, expected list, size, prune, verify, or clear"))); }
+        };
+        if !matches!(command, CacheCommand::Prune { .. }) && (older_than.is_some() || keep.is_some()) {
+            return Err(Error::Args(format!("--older-than and --keep can only be used with msegen cache prune")));
+        }
+        Ok(Args::Cache(args, command))
     }
 
     fn handle_long_arg(arg: &str, raw_args: &mut impl Iterator<Item = String>, args: &mut ArgsRegular) -> Result<bool, Error> {
@@ -343,6 +722,30 @@ impl Args {
     }
 }
 
+/// The config files which are loaded if neither `--config` nor `--no-config` is given, in the order in which they're applied.
+pub fn default_config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::default();
+    #[cfg(unix)] { if let Ok(config_home) = xdg_basedir::get_config_home() { paths.push(config_home.join("magic-set-generator").join("config.toml")); } }
+    #[cfg(not(unix))] { if let Some(proj_dirs) = ProjectDirs::from("net", "Fenhl", "Magic Set Generator") { paths.push(proj_dirs.config_dir().join("config.toml")); } }
+    paths.push(PathBuf::from("msegen.toml"));
+    paths
+}
+
+fn env_var_name(long: &str) -> String {
+    format!("MSEGEN_{}", long.to_uppercase().replace('-', "_"))
+}
+
+fn env_var(long: &str) -> Result<Option<String>, Error> {
+    let var_name = env_var_name(long);
+    match env::var(&var_name) {
+        // treated as unset so that e.g. `MSEGEN_SET_CODE= msegen` doesn't override a configured value
+        Ok(value) if value.is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => Err(Error::Args(format!("environment variable {} is not valid UTF-8", var_name)))
+    }
+}
+
 fn auto_card_numbers(args: &mut ArgsRegular) -> Result<(), Error> {
     args.auto_card_numbers = true;
     Ok(())
@@ -365,21 +768,69 @@ fn command_all(args: &mut ArgsRegular, _: Vec<String>) -> Result<(), Error> {
     Ok(())
 }
 
+fn command_tappedout(args: &mut ArgsRegular, mut cmd_args: Vec<String>) -> Result<(), Error> {
+    args.decklists.push(cmd_args.remove(0));
+    Ok(())
+}
+
+fn copies(args: &mut ArgsRegular) -> Result<(), Error> {
+    args.copies = true;
+    Ok(())
+}
+
 fn copyright(args: &mut ArgsRegular, copyright_text: &str) -> Result<(), Error> {
     args.copyright = copyright_text.into();
     Ok(())
 }
 
+fn crop_images(args: &mut ArgsRegular) -> Result<(), Error> {
+    args.crop_images = true;
+    Ok(())
+}
+
 fn database(args: &mut ArgsRegular, db_path: &str) -> Result<(), Error> {
     args.database = Some(db_path.into());
     Ok(())
 }
 
+fn find_cards(args: &mut ArgsRegular, exe_path: &str) -> Result<(), Error> {
+    args.find_cards = Some(exe_path.into());
+    Ok(())
+}
+
 fn holofoil_stamps(args: &mut ArgsRegular) -> Result<(), Error> {
     args.holofoil_stamps = true;
     Ok(())
 }
 
+fn image_jpeg_quality(args: &mut ArgsRegular, quality: &str) -> Result<(), Error> {
+    args.image_jpeg_quality = Some(quality.parse().ok().filter(|quality| (1..=100).contains(quality)).ok_or_else(|| Error::Args(format!("invalid JPEG quality: {} (must be between 1 and 100)", quality)))?);
+    Ok(())
+}
+
+fn image_manifest(args: &mut ArgsRegular, manifest_path: &str) -> Result<(), Error> {
+    args.image_manifest.load(manifest_path)
+}
+
+fn image_max_size(args: &mut ArgsRegular, size: &str) -> Result<(), Error> {
+    args.image_max_size = Some(size.parse().ok().filter(|&size| size > 0).ok_or_else(|| Error::Args(format!("invalid maximum image size: {}", size)))?);
+    Ok(())
+}
+
+fn image_sources(args: &mut ArgsRegular, sources: &str) -> Result<(), Error> {
+    args.image_sources = sources.split(',')
+        .map(str::trim)
+        .filter(|source| !source.is_empty())
+        .map(str::parse)
+        .collect::<Result<_, _>>()?;
+    Ok(())
+}
+
+fn image_url_template(args: &mut ArgsRegular, template: &str) -> Result<(), Error> {
+    args.image_url_template = Some(template.into());
+    Ok(())
+}
+
 fn images(args: &mut ArgsRegular, img_dir: &str) -> Result<(), Error> {
     args.images = Some(img_dir.into());
     Ok(())
@@ -406,10 +857,28 @@ fn include_vanguards_on(args: &mut ArgsRegular) -> Result<(), Error> {
 }
 
 fn input(args: &mut ArgsRegular, in_path: &str) -> Result<(), Error> {
-    BufReader::new(File::open(in_path).at(in_path)?)
-        .lines()
-        .map(|line| line.at(in_path).and_then(|line| args.handle_line(line)))
-        .collect::<Result<_, _>>()?;
+    if Path::new(in_path).is_dir() {
+        if args.images.is_none() {
+            args.images = Some(in_path.into());
+        }
+        args.image_inputs.push(in_path.into());
+        return Ok(());
+    }
+    let text = fs::read_to_string(in_path).at(in_path)?;
+    match decklist::Format::detect(&text).annotate(in_path)? {
+        decklist::Format::Plain => for line in text.lines() {
+            args.handle_line(line.to_owned())?;
+        },
+        decklist::Format::Arena => for line in decklist::arena_card_lines(&text) {
+            args.handle_line(line.to_owned()).annotate(in_path)?;
+        },
+        decklist::Format::Mtgo => for (card_name, quantity) in decklist::mtgo_cards(&text).annotate(in_path)? {
+            args.add_card(card_name, quantity);
+        },
+        decklist::Format::Cockatrice => for (card_name, quantity) in decklist::cockatrice_cards(&text).annotate(in_path)? {
+            args.add_card(card_name, quantity);
+        }
+    }
     Ok(())
 }
 
@@ -423,6 +892,26 @@ fn lore_seeker_images(args: &mut ArgsRegular, img_dir: &str) -> Result<(), Error
     Ok(())
 }
 
+fn no_auto_card_numbers(args: &mut ArgsRegular) -> Result<(), Error> {
+    args.auto_card_numbers = false;
+    Ok(())
+}
+
+fn no_copies(args: &mut ArgsRegular) -> Result<(), Error> {
+    args.copies = false;
+    Ok(())
+}
+
+fn no_crop_images(args: &mut ArgsRegular) -> Result<(), Error> {
+    args.crop_images = false;
+    Ok(())
+}
+
+fn no_holofoil_stamps(args: &mut ArgsRegular) -> Result<(), Error> {
+    args.holofoil_stamps = false;
+    Ok(())
+}
+
 fn no_images(args: &mut ArgsRegular) -> Result<(), Error> {
     args.no_images = true;
     Ok(())
@@ -433,11 +922,26 @@ fn no_lore_seeker_images(args: &mut ArgsRegular) -> Result<(), Error> {
     Ok(())
 }
 
+fn no_offline(args: &mut ArgsRegular) -> Result<(), Error> {
+    args.offline = false;
+    Ok(())
+}
+
 fn no_scryfall_images(args: &mut ArgsRegular) -> Result<(), Error> {
     args.no_scryfall_images = true;
     Ok(())
 }
 
+fn no_strict(args: &mut ArgsRegular) -> Result<(), Error> {
+    args.strict = false;
+    Ok(())
+}
+
+fn no_verbose(args: &mut ArgsRegular) -> Result<(), Error> {
+    args.verbose = false;
+    Ok(())
+}
+
 fn offline(args: &mut ArgsRegular) -> Result<(), Error> {
     args.offline = true;
     Ok(())
@@ -448,11 +952,36 @@ fn output(args: &mut ArgsRegular, out_path: &str) -> Result<(), Error> {
     Ok(())
 }
 
+fn overrides(args: &mut ArgsRegular, overrides_path: &str) -> Result<(), Error> {
+    args.overrides.load(overrides_path)
+}
+
+fn query_backend(args: &mut ArgsRegular, backend: &str) -> Result<(), Error> {
+    args.query_backend = Some(backend.parse()?);
+    Ok(())
+}
+
 fn schemes_output(args: &mut ArgsRegular, out_path: &str) -> Result<(), Error> {
     args.schemes_output = Some(out_path.parse()?);
     Ok(())
 }
 
+fn scryfall_api_url(args: &mut ArgsRegular, url: &str) -> Result<(), Error> {
+    let mut url = url.parse::<Url>()?;
+    if url.cannot_be_a_base() { return Err(Error::Args(format!("invalid Scryfall API URL: {}", url))); }
+    // API paths are joined onto this URL, so make sure its last path segment isn't replaced
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    args.scryfall_api_url = url;
+    Ok(())
+}
+
+fn scryfall_image_variant(args: &mut ArgsRegular, variant: &str) -> Result<(), Error> {
+    args.scryfall_image_variant = variant.parse()?;
+    Ok(())
+}
+
 fn scryfall_images(args: &mut ArgsRegular, img_dir: &str) -> Result<(), Error> {
     args.scryfall_images = Some(img_dir.into());
     Ok(())
@@ -463,6 +992,54 @@ fn set_code(args: &mut ArgsRegular, set_code: &str) -> Result<(), Error> {
     Ok(())
 }
 
+fn set_description(args: &mut ArgsRegular, description: &str) -> Result<(), Error> {
+    args.set_description = Some(description.into());
+    Ok(())
+}
+
+fn set_language(args: &mut ArgsRegular, language: &str) -> Result<(), Error> {
+    args.set_language = language.to_uppercase();
+    Ok(())
+}
+
+fn set_symbol(args: &mut ArgsRegular, symbol_path: &str) -> Result<(), Error> {
+    args.set_symbol = Some(symbol_path.into());
+    Ok(())
+}
+
+fn set_title(args: &mut ArgsRegular, title: &str) -> Result<(), Error> {
+    args.set_title = title.into();
+    Ok(())
+}
+
+fn shared_face_images(args: &mut ArgsRegular, strategy: &str) -> Result<(), Error> {
+    args.shared_face_images = strategy.parse()?;
+    Ok(())
+}
+
+fn strict(args: &mut ArgsRegular) -> Result<(), Error> {
+    args.strict = true;
+    Ok(())
+}
+
+fn styling(args: &mut ArgsRegular, styling_option: &str) -> Result<(), Error> {
+    let (stylesheet, option) = {
+        let mut split = styling_option.splitn(2, ':');
+        (split.next().expect("splitn returned no items"), split.next().ok_or_else(|| Error::Args(format!("missing stylesheet name in styling option: {}", styling_option)))?)
+    };
+    let (key, value) = {
+        let mut split = option.splitn(2, '=');
+        (split.next().expect("splitn returned no items"), split.next().ok_or_else(|| Error::Args(format!("missing value in styling option: {}", styling_option)))?)
+    };
+    args.styling.entry(stylesheet.trim().into()).or_default().insert(key.trim().into(), value.trim().into());
+    Ok(())
+}
+
+fn tappedout_url(args: &mut ArgsRegular, url: &str) -> Result<(), Error> {
+    args.tappedout_url = url.parse()?;
+    Ok(())
+}
+
 fn vanguards_output(args: &mut ArgsRegular, out_path: &str) -> Result<(), Error> {
     args.vanguards_output = Some(out_path.parse()?);
     Ok(())
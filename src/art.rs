@@ -1,75 +1,201 @@
 use {
     std::{
-        cell::RefCell,
-        collections::HashMap,
+        collections::{
+            BTreeMap,
+            HashMap
+        },
+        ffi::OsStr,
         fs::{
             self,
             File
         },
         io::{
             self,
+            Cursor,
             prelude::*
         },
-        path::PathBuf,
-        sync::Arc,
-        thread,
-        time::{
-            Duration,
-            Instant
-        }
+        path::{
+            Path,
+            PathBuf
+        },
+        sync::Arc
+    },
+    image::{
+        DynamicImage,
+        GenericImageView as _,
+        ImageFormat,
+        ImageOutputFormat,
+        imageops::FilterType
     },
     itertools::Itertools as _,
-    mtg::card::Card,
+    lazy_static::lazy_static,
+    mtg::card::{
+        Card,
+        Db
+    },
     parking_lot::Mutex,
+    percent_encoding::{
+        AsciiSet,
+        NON_ALPHANUMERIC,
+        utf8_percent_encode
+    },
+    regex::Regex,
     reqwest::blocking::{
         Client,
         Response
     },
-    serde::Deserialize,
+    serde::{
+        Deserialize,
+        Serialize
+    },
     url::Url,
     crate::{
-        args::ArgsRegular,
+        args::{
+            ArgsRegular,
+            ArtSource,
+            Printing,
+            ScryfallImageVariant,
+            SharedFaceImages
+        },
+        manifest::ImageManifest,
+        names,
+        scryfall,
         util::{
             Error,
-            IoResultExt as _
+            IoResultExt as _,
+            card_faces
         }
     }
 };
 #[cfg(not(unix))] use directories::ProjectDirs;
 
-#[derive(Debug, Deserialize)]
+pub(crate) const IMAGE_EXTENSIONS: [&str; 6] = ["png", "PNG", "jpg", "JPG", "jpeg", "JPEG"];
+
+/// Everything except ASCII letters, digits, and `-._~`, the unreserved characters in URLs.
+const URL_TEMPLATE_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+const STANDARD_ART_ASPECT_RATIO: f64 = 1.36;
+
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"
+];
+
+lazy_static! {
+    static ref LORE_SEEKER_ARTIST_REGEX: Regex = Regex::new("<a [^>]*href=\"/artist/[^\"]*\"[^>]*>([^<]+)</a>").expect("failed to build Lore Seeker artist regex");
+}
+
+#[derive(Debug, Clone, Deserialize)]
 struct ScryfallData {
     artist: String,
     card_faces: Option<Vec<ScryfallCardFace>>,
-    image_uris: Option<ScryfallImageUris>
+    collector_number: String,
+    image_uris: Option<ScryfallImageUris>,
+    rarity: String,
+    set: String
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct ScryfallCardFace {
     name: String,
     image_uris: Option<ScryfallImageUris>
 }
 
-#[derive(Debug, Deserialize)]
+/// Scryfall doesn't guarantee that every variant is available, so all of them are optional.
+#[derive(Debug, Clone, Deserialize)]
 struct ScryfallImageUris {
-    art_crop: Url
+    art_crop: Option<Url>,
+    border_crop: Option<Url>,
+    large: Option<Url>,
+    png: Option<Url>
+}
+
+impl ScryfallImageUris {
+    /// The full card variants can replace each other, but the art crop can't be replaced since it's used differently.
+    fn get(&self, variant: ScryfallImageVariant) -> Option<(&Url, ScryfallImageVariant)> {
+        let fallbacks: &[ScryfallImageVariant] = match variant {
+            ScryfallImageVariant::ArtCrop => &[ScryfallImageVariant::ArtCrop],
+            ScryfallImageVariant::BorderCrop => &[ScryfallImageVariant::BorderCrop, ScryfallImageVariant::Large, ScryfallImageVariant::Png],
+            ScryfallImageVariant::Large => &[ScryfallImageVariant::Large, ScryfallImageVariant::Png, ScryfallImageVariant::BorderCrop],
+            ScryfallImageVariant::Png => &[ScryfallImageVariant::Png, ScryfallImageVariant::Large, ScryfallImageVariant::BorderCrop]
+        };
+        fallbacks.iter().find_map(|&variant| match variant {
+            ScryfallImageVariant::ArtCrop => self.art_crop.as_ref(),
+            ScryfallImageVariant::BorderCrop => self.border_crop.as_ref(),
+            ScryfallImageVariant::Large => self.large.as_ref(),
+            ScryfallImageVariant::Png => self.png.as_ref()
+        }.map(|url| (url, variant)))
+    }
 }
 
 #[derive(Debug)]
 enum ImageSource {
     Path(PathBuf),
-    ScryfallUrl(Url),
+    ScryfallUrl(Url, ScryfallImageVariant),
+    Url(Url),
     LoreSeekerUrl {
         set_code: String,
         collector_number: String
     }
 }
 
+/// Information about where an image was downloaded from, saved next to the image file as `<image file name>.json`.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub(crate) struct ImageMetadata {
+    pub(crate) artist: Option<String>,
+    source_url: Option<Url>,
+    set_code: Option<String>,
+    collector_number: Option<String>,
+    /// The variant requested using `--scryfall-image-variant`, not the fallback that may have been downloaded instead, so a fallback isn't downloaded again on every run.
+    scryfall_image_variant: Option<String>,
+    #[serde(default)]
+    shared: bool
+}
+
+impl ImageMetadata {
+    /// Returns the default (empty) metadata if there is none, e.g. for images which weren't downloaded by MSG.
+    fn load(img_path: &Path) -> ImageMetadata {
+        fs::read_to_string(ImageMetadata::sidecar_path(img_path)).ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, img_path: &Path) -> Result<(), Error> {
+        let sidecar_path = ImageMetadata::sidecar_path(img_path);
+        serde_json::to_writer_pretty(File::create(&sidecar_path).at(&sidecar_path)?, self)?;
+        Ok(())
+    }
+
+    /// The metadata file is named after the full file name of the image, since images of the same card can be saved with different extensions.
+    pub(crate) fn sidecar_path(img_path: &Path) -> PathBuf {
+        let mut sidecar_path = img_path.as_os_str().to_owned();
+        sidecar_path.push(".json");
+        PathBuf::from(sidecar_path)
+    }
+
+    /// Images which weren't downloaded from Scryfall are assumed to match.
+    fn matches_variant(&self, variant: ScryfallImageVariant) -> bool {
+        self.scryfall_image_variant.as_ref().map_or(true, |recorded| recorded == variant.api_name())
+    }
+
+    /// Images without a recorded printing are assumed to match.
+    fn matches_printing(&self, printing: &Printing) -> bool {
+        self.set_code.as_ref().map_or(true, |set_code| set_code.eq_ignore_ascii_case(&printing.set_code))
+        && match (&self.collector_number, &printing.collector_number) {
+            (Some(collector_number), Some(selected)) => collector_number.eq_ignore_ascii_case(selected),
+            (_, _) => true
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Image {
-    pub artist: Option<String>,
     card: Card,
     pub id: usize,
+    pub(crate) metadata: ImageMetadata,
     source: ImageSource
 }
 
@@ -78,16 +204,23 @@ impl Image {
         Image {
             card,
             id: 0,
-            artist: None,
+            metadata: ImageMetadata::load(&path),
             source: ImageSource::Path(path)
         }
     }
 
-    fn lore_seeker(card: Card, set_code: &str, collector_number: &str) -> Image {
+    fn lore_seeker(card: Card, lore_seeker_hostname: Option<&str>, set_code: &str, collector_number: &str, artist: Option<String>) -> Image {
         Image {
             card,
             id: 0,
-            artist: None,
+            metadata: ImageMetadata {
+                artist,
+                source_url: Url::parse(&format!("https://{}/card/{}/{}", lore_seeker_hostname.unwrap_or("lore-seeker.cards"), set_code, collector_number)).ok(),
+                set_code: Some(set_code.into()),
+                collector_number: Some(collector_number.into()),
+                scryfall_image_variant: None,
+                shared: false
+            },
             source: ImageSource::LoreSeekerUrl {
                 set_code: set_code.into(),
                 collector_number: collector_number.into()
@@ -95,17 +228,44 @@ impl Image {
         }
     }
 
-    fn scryfall(card: Card, url: Url, artist: String) -> Image {
+    fn scryfall(card: Card, url: Url, scryfall_data: &ScryfallData, requested_variant: ScryfallImageVariant, variant: ScryfallImageVariant, shared: bool) -> Image {
+        Image {
+            card,
+            id: 0,
+            metadata: ImageMetadata {
+                artist: Some(scryfall_data.artist.clone()),
+                source_url: Some(url.clone()),
+                set_code: Some(scryfall_data.set.clone()),
+                collector_number: Some(scryfall_data.collector_number.clone()),
+                scryfall_image_variant: Some(requested_variant.api_name().into()),
+                shared
+            },
+            source: ImageSource::ScryfallUrl(url, variant)
+        }
+    }
+
+    fn url(card: Card, url: Url, printing: Option<&Printing>) -> Image {
         Image {
             card,
             id: 0,
-            artist: Some(artist),
-            source: ImageSource::ScryfallUrl(url)
+            metadata: ImageMetadata {
+                artist: None,
+                source_url: Some(url.clone()),
+                set_code: printing.map(|printing| printing.set_code.clone()),
+                collector_number: printing.and_then(|printing| printing.collector_number.clone()),
+                scryfall_image_variant: None,
+                shared: false
+            },
+            source: ImageSource::Url(url)
         }
     }
 
     fn filename(&self) -> String {
-        normalized_image_name(&self.card)
+        if self.metadata.shared {
+            normalized_image_name(&self.card.primary())
+        } else {
+            normalized_image_name(&self.card)
+        }
     }
 
     fn open(&mut self, config: &ArtHandlerConfig) -> Result<Box<dyn Read>, Error> {
@@ -113,13 +273,31 @@ impl Image {
             ImageSource::Path(ref path) => File::open(path)
                 .map(|f| Box::new(f) as Box<dyn Read>)
                 .at(path),
-            ImageSource::ScryfallUrl(ref url) => {
+            ImageSource::ScryfallUrl(ref url, variant) => {
                 let mut resp = Image::scryfall_download(config, url)?;
-                if let Some(ref img_dir) = config.scryfall_images.as_ref().or(config.images.as_ref()).or(img_cache().as_ref()) {
+                if let Some(ref img_dir) = download_dir(config.scryfall_images.as_ref(), config.images.as_ref()) {
                     fs::create_dir_all(img_dir).at(img_dir)?;
-                    let img_path = img_dir.join(format!("{}.png", self.filename()));
+                    let img_path = img_dir.join(format!("{}.{}", self.filename(), variant.file_ext()));
                     io::copy(&mut resp, &mut File::create(&img_path).at(&img_path)?).at(&img_path)?;
-                    //TODO save artist credit in exif data
+                    self.metadata.save(&img_path)?;
+                    File::open(&img_path).map(|f| Box::new(f) as Box<dyn Read>).at(img_path)
+                } else {
+                    Ok(Box::new(resp))
+                }
+            }
+            ImageSource::Url(ref url) => {
+                let mut resp = config.client.get(url.as_str())
+                    .send()?
+                    .error_for_status()?;
+                if let Some(ref img_dir) = download_dir(None, config.images.as_ref()) {
+                    fs::create_dir_all(img_dir).at(img_dir)?;
+                    let file_ext = Path::new(url.path()).extension()
+                        .and_then(OsStr::to_str)
+                        .filter(|ext| IMAGE_EXTENSIONS.contains(ext))
+                        .unwrap_or("png");
+                    let img_path = img_dir.join(format!("{}.{}", self.filename(), file_ext));
+                    io::copy(&mut resp, &mut File::create(&img_path).at(&img_path)?).at(&img_path)?;
+                    self.metadata.save(&img_path)?;
                     File::open(&img_path).map(|f| Box::new(f) as Box<dyn Read>).at(img_path)
                 } else {
                     Ok(Box::new(resp))
@@ -128,11 +306,11 @@ impl Image {
             ImageSource::LoreSeekerUrl { ref set_code, ref collector_number } => {
                 let mut resp = lore_seeker::get(config.lore_seeker_hostname.as_deref(), format!("/art/{}/{}.jpg", set_code, collector_number))
                     .or_else(|_| lore_seeker::get(config.lore_seeker_hostname.as_deref(), format!("/art/{}/{}.png", set_code, collector_number)))?;
-                if let Some(ref img_dir) = config.lore_seeker_images.as_ref().or(config.images.as_ref()).or(img_cache().as_ref()) {
+                if let Some(ref img_dir) = download_dir(config.lore_seeker_images.as_ref(), config.images.as_ref()) {
                     fs::create_dir_all(img_dir).at(img_dir)?;
                     let img_path = img_dir.join(format!("{}.jpg", self.filename()));
                     io::copy(&mut resp, &mut File::create(&img_path).at(&img_path)?).at(&img_path)?;
-                    //TODO save artist credit in exif data, if not already present
+                    self.metadata.save(&img_path)?;
                     File::open(&img_path).map(|f| Box::new(f) as Box<dyn Read>).at(img_path)
                 } else {
                     Ok(Box::new(resp))
@@ -152,35 +330,71 @@ impl Image {
 #[derive(Debug, Clone)]
 struct ArtHandlerConfig {
     client: Client,
+    crop_images: bool,
+    image_jpeg_quality: Option<u8>,
+    image_manifest: ImageManifest,
+    image_sources: Vec<ArtSource>,
+    image_url_template: Option<String>,
+    image_max_size: Option<u32>,
     lore_seeker_hostname: Option<String>,
-    scryfall_rate_limit: RefCell<Option<Instant>>,
+    mtg_json: Option<PathBuf>,
     images: Option<PathBuf>,
     lore_seeker_images: Option<PathBuf>,
     no_images: bool,
     no_lore_seeker_images: bool,
     no_scryfall_images: bool,
-    scryfall_images: Option<PathBuf>
+    offline: bool,
+    printings: BTreeMap<String, Printing>,
+    scryfall_api_url: Url,
+    scryfall_image_variant: ScryfallImageVariant,
+    scryfall_images: Option<PathBuf>,
+    set_symbol: Option<PathBuf>,
+    shared_face_images: SharedFaceImages
 }
 
 impl ArtHandlerConfig {
     fn scryfall_request(&self, url: &Url) -> Result<Response, reqwest::Error> {
-        if let Some(next_request_time) = *self.scryfall_rate_limit.borrow() {
-            let now = Instant::now();
-            if next_request_time > now {
-                thread::sleep(next_request_time - now);
+        scryfall::request(&self.client, url).and_then(|resp| resp.error_for_status())
+    }
+
+    fn processes_images(&self) -> bool {
+        self.crop_images || self.image_jpeg_quality.is_some() || self.image_max_size.is_some()
+    }
+
+    /// The image keeps its format unless `--image-jpeg-quality` is given. Returns `None` if the image should be used unmodified.
+    fn process_image(&self, buf: &[u8], art_aspect_ratio: Option<f64>) -> Result<Option<Vec<u8>>, Error> {
+        let format = image::guess_format(buf)?;
+        let mut image = image::load_from_memory_with_format(buf, format)?;
+        let dimensions = image.dimensions();
+        if let Some(art_aspect_ratio) = art_aspect_ratio {
+            image = center_crop(image, art_aspect_ratio);
+        }
+        if let Some(max_size) = self.image_max_size {
+            if image.width() > max_size || image.height() > max_size {
+                image = image.resize(max_size, max_size, FilterType::Lanczos3);
             }
         }
-        let result = self.client.get(url.as_str()) //TODO remove this url v1 to v2 compat conversion
-            .send()
-            .and_then(|resp| resp.error_for_status());
-        self.scryfall_rate_limit.replace(Some(Instant::now() + Duration::from_millis(100)));
-        result
+        let mut encoded = Vec::default();
+        match (self.image_jpeg_quality, format) {
+            (None, _) if image.dimensions() == dimensions => { return Ok(None); }
+            (Some(quality), _) => { DynamicImage::ImageRgb8(image.to_rgb8()).write_to(&mut encoded, ImageOutputFormat::Jpeg(quality))?; }
+            (None, ImageFormat::Jpeg) => { DynamicImage::ImageRgb8(image.to_rgb8()).write_to(&mut encoded, ImageOutputFormat::Jpeg(DEFAULT_JPEG_QUALITY))?; }
+            (None, _) => { image.write_to(&mut encoded, ImageOutputFormat::Png)?; }
+        }
+        Ok(Some(encoded))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct ArtHandler {
     set_images: HashMap<Card, Arc<Mutex<Image>>>,
+    /// Images shared by all faces of a multi-part card, keyed by the name of the card's primary face, so that they're only added to the set file once.
+    shared_images: HashMap<String, Arc<Mutex<Image>>>,
+    /// `None` if the request failed.
+    scryfall_cache: HashMap<String, Option<ScryfallData>>,
+    stylesheets: HashMap<String, String>,
+    printing_artists: Option<HashMap<(String, String), String>>,
+    image_warnings: Vec<String>,
     config: ArtHandlerConfig
 }
 
@@ -188,97 +402,429 @@ impl ArtHandler {
     pub fn new(args: &ArgsRegular, client: Client) -> ArtHandler {
         ArtHandler {
             set_images: HashMap::default(),
+            shared_images: HashMap::default(),
+            scryfall_cache: HashMap::default(),
+            stylesheets: HashMap::default(),
+            printing_artists: None,
+            image_warnings: Vec::default(),
             config: ArtHandlerConfig {
                 client,
+                crop_images: args.crop_images,
+                image_jpeg_quality: args.image_jpeg_quality,
+                image_manifest: args.image_manifest.clone(),
+                image_sources: args.image_sources.clone(),
+                image_url_template: args.image_url_template.clone(),
+                image_max_size: args.image_max_size,
                 lore_seeker_hostname: args.lore_seeker_hostname.clone(),
-                scryfall_rate_limit: RefCell::default(),
+                mtg_json: names::mtg_json_path(args),
                 images: args.images.clone(),
                 lore_seeker_images: args.lore_seeker_images.clone(),
                 no_images: args.no_images,
                 no_lore_seeker_images: args.no_lore_seeker_images(),
                 no_scryfall_images: args.no_scryfall_images(),
-                scryfall_images: args.scryfall_images.clone()
+                offline: args.offline,
+                printings: args.printings.clone(),
+                scryfall_api_url: args.scryfall_api_url.clone(),
+                scryfall_image_variant: args.scryfall_image_variant,
+                scryfall_images: args.scryfall_images.clone(),
+                set_symbol: args.set_symbol.clone(),
+                shared_face_images: args.shared_face_images
             }
         }
     }
 
-    fn add_image(&mut self, mut image: Image) -> Option<Arc<Mutex<Image>>> {
+    fn add_image(&mut self, card: &Card, mut image: Image) -> Option<Arc<Mutex<Image>>> {
+        let shared = image.metadata.shared;
+        if shared {
+            let primary_name = card.primary().to_string();
+            if let Some(image_arc) = self.shared_images.get(&primary_name).cloned() {
+                self.set_images.insert(card.clone(), image_arc.clone());
+                return Some(image_arc);
+            }
+            image.card = card.primary();
+        }
         image.id = self.set_images.len() + 1;
-        let card = image.card.clone();
         let image_arc = Arc::new(Mutex::new(image));
-        self.set_images.insert(card, image_arc.clone());
+        self.set_images.insert(card.clone(), image_arc.clone());
+        if shared {
+            self.shared_images.insert(card.primary().to_string(), image_arc.clone());
+        }
         Some(image_arc)
     }
 
+    pub(crate) fn open_set_symbol(&self) -> Result<Option<File>, Error> {
+        Ok(if let Some(ref symbol_path) = self.config.set_symbol {
+            Some(File::open(symbol_path).at(symbol_path)?)
+        } else {
+            None
+        })
+    }
+
+    pub(crate) fn set_stylesheet(&mut self, card: &Card, stylesheet: &str) {
+        self.stylesheets.insert(card.primary().to_string(), stylesheet.into());
+    }
+
+    /// Images which can't be processed are opened unmodified, with a warning added to `image_warnings`.
     pub(crate) fn open_images(&mut self) -> impl Iterator<Item = Result<(usize, Box<dyn Read>), Error>> + '_ {
         let config = self.config.clone();
-        self.set_images.values().map(move |img| {
+        let stylesheets = &self.stylesheets;
+        let image_warnings = &mut self.image_warnings;
+        self.set_images.values().unique_by(|img| img.lock().id).map(move |img| -> Result<_, Error> {
             let mut img = img.lock();
-            img.open(&config).map(|f| (img.id, f))
+            let mut reader = img.open(&config)?;
+            if !config.processes_images() { return Ok((img.id, reader)); }
+            let mut buf = Vec::default();
+            reader.read_to_end(&mut buf).at_unknown()?;
+            let aspect_ratio = if config.crop_images {
+                stylesheets.get(&img.card.primary().to_string()).and_then(|stylesheet| {
+                    let aspect_ratio = art_aspect_ratio(stylesheet);
+                    if aspect_ratio.is_none() {
+                        let warning = format!("the art box of stylesheet {} is unknown, images for cards using it are not cropped", stylesheet);
+                        if !image_warnings.contains(&warning) { image_warnings.push(warning); }
+                    }
+                    aspect_ratio
+                })
+            } else {
+                None
+            };
+            let buf = match config.process_image(&buf, aspect_ratio) {
+                Ok(Some(processed)) => processed,
+                Ok(None) => buf,
+                Err(e) => {
+                    let warning = format!("could not process image for {}, using it unmodified: {}", img.card, e);
+                    if !image_warnings.contains(&warning) { image_warnings.push(warning); }
+                    buf
+                }
+            };
+            Ok((img.id, Box::new(Cursor::new(buf)) as Box<dyn Read>))
         })
     }
 
+    pub fn image_warnings(&self) -> &[String] {
+        &self.image_warnings
+    }
+
+    pub(crate) fn register_image_path(&mut self, card: &Card, path: PathBuf) -> Option<Arc<Mutex<Image>>> {
+        if self.config.no_images { return None; }
+        if let Some(image) = self.set_images.get(card) { return Some(Arc::clone(image)); }
+        if !path.exists() {
+            self.image_warnings.push(format!("override image for {} not found, using other image sources: {}", card, path.display()));
+            return self.register_image_for(card);
+        }
+        self.add_image(card, Image::path(card.clone(), path))
+    }
+
     pub(crate) fn register_image_for(&mut self, card: &Card) -> Option<Arc<Mutex<Image>>> {
         if self.config.no_images { return None; }
         if let Some(image) = self.set_images.get(card) { return Some(Arc::clone(image)); }
-        let mut img_dirs = vec![self.config.images.clone(), self.config.scryfall_images.clone(), self.config.lore_seeker_images.clone()];
-        if self.config.images.is_none() && (self.config.scryfall_images.is_none() || self.config.lore_seeker_images.is_none()) {
-            img_dirs.push(img_cache());
+        if let Some(entry) = self.config.image_manifest.get(card).cloned() {
+            let image = match (entry.path, entry.url) {
+                (Some(path), _) if path.exists() => Some(Image::path(card.clone(), path)),
+                // in offline mode, URLs are skipped in favor of the other image sources
+                (_, Some(url)) => if self.config.offline {
+                    None
+                } else if self.url_exists(&url) {
+                    Some(Image::url(card.clone(), url, None))
+                } else {
+                    self.image_warnings.push(format!("image manifest URL for {} could not be loaded, using other image sources: {}", card, url));
+                    None
+                },
+                (Some(path), None) => {
+                    self.image_warnings.push(format!("image manifest file for {} not found, using other image sources: {}", card, path.display()));
+                    None
+                }
+                (None, None) => None
+            };
+            if let Some(mut image) = image {
+                if entry.artist.is_some() { image.metadata.artist = entry.artist; }
+                return self.add_image(card, image);
+            }
         }
+        for source in self.config.image_sources.clone() {
+            let image = match source {
+                ArtSource::Local => self.local_image(card),
+                ArtSource::UrlTemplate => if self.config.offline { None } else { self.url_template_image(card) },
+                ArtSource::Scryfall => if self.config.no_scryfall_images { None } else { self.scryfall_image(card) },
+                ArtSource::LoreSeeker => if self.config.no_lore_seeker_images { None } else { self.lore_seeker_image(card) }
+            };
+            if let Some(image) = image {
+                return self.add_image(card, image);
+            }
+        }
+        None
+    }
+
+    fn local_image(&self, card: &Card) -> Option<Image> {
+        let img_dirs = image_dirs(self.config.images.as_ref(), self.config.scryfall_images.as_ref(), self.config.lore_seeker_images.as_ref());
+        // an image shared by all faces of the card is saved under the name of the primary face
+        let shared_names = if card.is_alt() && self.config.shared_face_images == SharedFaceImages::All { image_names(&card.primary()) } else { Vec::default() };
         for img_dir in img_dirs {
-            if let Some(path) = img_dir {
-                for file_ext in &["png", "PNG", "jpg", "JPG", "jpeg", "JPEG"] {
-                    let image_path = path.join(format!("{}.{}", normalized_image_name(card), file_ext));
+            for (image_name, shared) in image_names(card).into_iter().map(|name| (name, false)).chain(shared_names.iter().cloned().map(|name| (name, true))) {
+                for file_ext in &IMAGE_EXTENSIONS {
+                    let image_path = img_dir.join(format!("{}.{}", image_name, file_ext));
                     if image_path.exists() {
-                        return self.add_image(Image::path(card.clone(), image_path)); //TODO artist from exif
+                        let image = Image::path(card.clone(), image_path);
+                        if shared && !image.metadata.shared { continue; }
+                        if image.metadata.shared && self.config.shared_face_images == SharedFaceImages::None { continue; }
+                        // a cached image from a different printing or of a different Scryfall image variant is downloaded again
+                        if !image.metadata.matches_variant(self.config.scryfall_image_variant) { continue; }
+                        if let Some(printing) = self.config.printings.get(&card.primary().to_string()) {
+                            if !image.metadata.matches_printing(printing) { continue; }
+                        }
+                        return Some(image);
                     }
                 }
             }
         }
-        if !self.config.no_scryfall_images {
-            let mut url = Url::parse("https://api.scryfall.com/cards/named").expect("failed to parse Scryfall API URL");
-            url.query_pairs_mut().append_pair("exact", &card.to_string());
-            if let Ok(resp) = self.config.scryfall_request(&url) {
-                if let Ok(scryfall_data) = resp.json::<ScryfallData>() {
-                    let artist = scryfall_data.artist;
-                    let art_crop = if let Some(image_uris) = scryfall_data.image_uris {
-                        Some(image_uris.art_crop)
-                    } else if let Some(card_faces) = scryfall_data.card_faces {
-                        card_faces.into_iter()
-                            .filter(|face| face.name == card.to_string())
-                            .filter_map(|face| face.image_uris)
-                            .collect_tuple()
-                            .map(|(image_uris,)| image_uris.art_crop)
-                    } else {
-                        None
-                    };
-                    if let Some(art_crop) = art_crop {
-                        return self.add_image(Image::scryfall(card.clone(), art_crop, artist));
-                    } //TODO else print error if in verbose mode
-                } //TODO else print error if in verbose mode
+        None
+    }
+
+    fn url_template_image(&self, card: &Card) -> Option<Image> {
+        let template = self.config.image_url_template.as_ref()?;
+        let printing = self.config.printings.get(&card.primary().to_string());
+        let mut url = template
+            .replace("{name}", &utf8_percent_encode(&card.to_string(), URL_TEMPLATE_ENCODE_SET).to_string())
+            .replace("{normalized_name}", &utf8_percent_encode(&normalized_image_name(card), URL_TEMPLATE_ENCODE_SET).to_string());
+        if url.contains("{set}") {
+            url = url.replace("{set}", &utf8_percent_encode(&printing?.set_code, URL_TEMPLATE_ENCODE_SET).to_string());
+        }
+        if url.contains("{number}") {
+            url = url.replace("{number}", &utf8_percent_encode(printing?.collector_number.as_ref()?, URL_TEMPLATE_ENCODE_SET).to_string());
+        }
+        let url = Url::parse(&url).ok()?; //TODO print error if in verbose mode
+        if !self.url_exists(&url) { return None; } //TODO print error if in verbose mode
+        Some(Image::url(card.clone(), url, printing))
+    }
+
+    fn url_exists(&self, url: &Url) -> bool {
+        // fall back to GET since some servers don't support HEAD requests
+        self.config.client.head(url.as_str())
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .or_else(|_| self.config.client.get(url.as_str()).send().and_then(|resp| resp.error_for_status()))
+            .is_ok()
+    }
+
+    fn scryfall_image(&mut self, card: &Card) -> Option<Image> {
+        let requested_variant = self.config.scryfall_image_variant;
+        let scryfall_data = self.scryfall_data(card).cloned()?; //TODO print error if in verbose mode
+        let (url_and_variant, shared) = if let Some(ref image_uris) = scryfall_data.image_uris {
+            let shared = scryfall_data.card_faces.is_some();
+            if shared {
+                match self.config.shared_face_images {
+                    SharedFaceImages::All => {}
+                    SharedFaceImages::Primary => if card.is_alt() { return None; },
+                    SharedFaceImages::None => { return None; }
+                }
+            }
+            (image_uris.get(requested_variant).map(|(url, variant)| (url.clone(), variant)), shared)
+        } else if let Some(ref card_faces) = scryfall_data.card_faces {
+            let url_and_variant = card_faces.iter()
+                .filter(|face| face.name == card.to_string())
+                .filter_map(|face| face.image_uris.as_ref())
+                .collect_tuple()
+                .and_then(|(image_uris,)| image_uris.get(requested_variant))
+                .map(|(url, variant)| (url.clone(), variant));
+            (url_and_variant, false)
+        } else {
+            (None, false)
+        };
+        let (url, variant) = url_and_variant?; //TODO print error if in verbose mode
+        Some(Image::scryfall(card.clone(), url, &scryfall_data, requested_variant, variant, shared))
+    }
+
+    fn lore_seeker_image(&mut self, card: &Card) -> Option<Image> {
+        if !card.is_alt() {
+            if let Some(Printing { set_code, collector_number: Some(collector_number) }) = self.config.printings.get(&card.primary().to_string()).cloned() {
+                let set_code = set_code.to_lowercase();
+                let artist = self.printing_artist(&set_code, &collector_number);
+                return Some(Image::lore_seeker(card.clone(), self.config.lore_seeker_hostname.as_deref(), &set_code, &collector_number, artist));
             }
         }
-        if !self.config.no_lore_seeker_images {
-            if let Ok((_, results)) = lore_seeker::resolve_query(self.config.lore_seeker_hostname.as_deref(), &format!("!{}", card)) {
-                if let Some(((_, url),)) = results.into_iter().collect_tuple() {
-                    if let Some(segments) = url.path_segments() {
-                        if let Some(("card", set_code, collector_number)) = segments.collect_tuple() {
-                            return self.add_image(Image::lore_seeker(card.clone(), set_code, collector_number)); //TODO get artist from Lore Seeker
-                        } //TODO else print error if in verbose mode
+        if let Ok((_, results)) = lore_seeker::resolve_query(self.config.lore_seeker_hostname.as_deref(), &format!("!{}", card)) {
+            if let Some(((_, url),)) = results.into_iter().collect_tuple() {
+                if let Some(segments) = url.path_segments() {
+                    if let Some(("card", set_code, collector_number)) = segments.collect_tuple() {
+                        let artist = self.printing_artist(set_code, collector_number);
+                        return Some(Image::lore_seeker(card.clone(), self.config.lore_seeker_hostname.as_deref(), set_code, collector_number, artist));
                     } //TODO else print error if in verbose mode
                 } //TODO else print error if in verbose mode
             } //TODO else print error if in verbose mode
-        }
+        } //TODO else print error if in verbose mode
         None
     }
+
+    fn printing_artist(&mut self, set_code: &str, collector_number: &str) -> Option<String> {
+        lore_seeker_artist(self.config.lore_seeker_hostname.as_deref(), set_code, collector_number)
+            .or_else(|| self.local_printing_artist(set_code, collector_number))
+    }
+
+    fn local_printing_artist(&mut self, set_code: &str, collector_number: &str) -> Option<String> {
+        if self.printing_artists.is_none() {
+            let mut printing_artists = HashMap::default();
+            if let Some(ref mtg_json) = self.config.mtg_json {
+                let _ = names::for_each_mtg_json_card(mtg_json, |card_set_code, card| { //TODO print error if in verbose mode
+                    if let (Some(number), Some(artist)) = (card.number, card.artist) {
+                        printing_artists.insert((card_set_code.to_lowercase(), number.to_lowercase()), artist);
+                    }
+                });
+            }
+            self.printing_artists = Some(printing_artists);
+        }
+        self.printing_artists.as_ref().expect("printing artists were just loaded").get(&(set_code.to_lowercase(), collector_number.to_lowercase())).cloned()
+    }
+
+    pub(crate) fn printing_rarity(&mut self, card: &Card) -> Option<&'static str> {
+        if self.config.offline || self.config.no_scryfall_images || !self.config.printings.contains_key(&card.primary().to_string()) { return None; }
+        match &self.scryfall_data(card)?.rarity[..] {
+            "common" => Some("common"),
+            "uncommon" => Some("uncommon"),
+            "rare" => Some("rare"),
+            "mythic" => Some("mythic rare"),
+            "special" | "bonus" => Some("special"),
+            _ => None
+        }
+    }
+
+    fn scryfall_data(&mut self, card: &Card) -> Option<&ScryfallData> {
+        let primary_name = card.primary().to_string();
+        if !self.scryfall_cache.contains_key(&primary_name) {
+            let mut url = match self.config.scryfall_api_url.join("cards") {
+                Ok(url) => url,
+                Err(_) => { return None; } //TODO print error if in verbose mode
+            };
+            match self.config.printings.get(&primary_name) {
+                Some(Printing { set_code, collector_number: Some(collector_number) }) => {
+                    url.path_segments_mut().expect("Scryfall API URL is cannot-be-a-base").push(&set_code.to_lowercase()).push(collector_number);
+                }
+                Some(Printing { set_code, collector_number: None }) => {
+                    url.path_segments_mut().expect("Scryfall API URL is cannot-be-a-base").push("named");
+                    url.query_pairs_mut().append_pair("exact", &card.to_string()).append_pair("set", set_code);
+                }
+                None => {
+                    url.path_segments_mut().expect("Scryfall API URL is cannot-be-a-base").push("named");
+                    url.query_pairs_mut().append_pair("exact", &card.to_string());
+                }
+            }
+            let scryfall_data = self.config.scryfall_request(&url).ok().and_then(|resp| resp.json::<ScryfallData>().ok()); //TODO print error if in verbose mode
+            self.scryfall_cache.insert(primary_name.clone(), scryfall_data);
+        }
+        self.scryfall_cache[&primary_name].as_ref()
+    }
+}
+
+fn lore_seeker_artist(lore_seeker_hostname: Option<&str>, set_code: &str, collector_number: &str) -> Option<String> {
+    let mut html = String::default();
+    lore_seeker::get(lore_seeker_hostname, format!("/card/{}/{}", set_code, collector_number)).ok()?.read_to_string(&mut html).ok()?; //TODO print error if in verbose mode
+    let captures = LORE_SEEKER_ARTIST_REGEX.captures(&html)?;
+    Some(captures[1].trim()
+        .replace("&#39;", "'")
+        .replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&"))
+}
+
+pub(crate) fn cards_from_image_dirs(db: &Db, img_dirs: &[PathBuf]) -> Result<(Vec<Card>, Vec<PathBuf>), Error> {
+    let mut cards = Vec::default();
+    let mut unmatched = Vec::default();
+    if img_dirs.is_empty() { return Ok((cards, unmatched)); }
+    let mut cards_by_image_name = HashMap::default();
+    for card in db.clone().into_iter().flat_map(|card| card_faces(&card)) {
+        for image_name in image_names(&card) {
+            cards_by_image_name.entry(image_name).or_insert_with(|| card.clone());
+        }
+    }
+    for img_dir in img_dirs {
+        for entry in fs::read_dir(img_dir).at(img_dir)? {
+            let path = entry.at(img_dir)?.path();
+            if !path.extension().and_then(OsStr::to_str).map_or(false, |ext| IMAGE_EXTENSIONS.contains(&ext)) { continue; }
+            match path.file_stem().and_then(OsStr::to_str).and_then(|image_name| cards_by_image_name.get(image_name)) {
+                Some(card) => { cards.push(card.clone()); }
+                None => { unmatched.push(path); }
+            }
+        }
+    }
+    Ok((cards, unmatched))
+}
+
+fn download_dir(source_images: Option<&PathBuf>, images: Option<&PathBuf>) -> Option<PathBuf> {
+    source_images.or(images).cloned().or_else(img_cache)
+}
+
+/// These include all directories to which images are downloaded.
+fn image_dirs(images: Option<&PathBuf>, scryfall_images: Option<&PathBuf>, lore_seeker_images: Option<&PathBuf>) -> Vec<PathBuf> {
+    vec![
+        images.cloned(),
+        download_dir(scryfall_images, images),
+        download_dir(lore_seeker_images, images),
+        download_dir(None, images)
+    ].into_iter().flatten().unique().collect()
+}
+
+/// The directories managed by the `msegen cache` commands.
+pub(crate) fn download_dirs(args: &ArgsRegular) -> Vec<PathBuf> {
+    image_dirs(args.images.as_ref(), args.scryfall_images.as_ref(), args.lore_seeker_images.as_ref())
+}
+
+fn art_aspect_ratio(stylesheet: &str) -> Option<f64> {
+    match stylesheet {
+        "m15-altered" | "m15-leveler" | "m15-mainframe-dfc" | "m15-ttk-conspiracy" => Some(STANDARD_ART_ASPECT_RATIO),
+        _ => None //TODO add art boxes of other stylesheets
+    }
+}
+
+fn center_crop(image: DynamicImage, aspect_ratio: f64) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    let (new_width, new_height) = if f64::from(width) / f64::from(height) > aspect_ratio {
+        ((f64::from(height) * aspect_ratio).round() as u32, height)
+    } else {
+        (width, (f64::from(width) / aspect_ratio).round() as u32)
+    };
+    if new_width == 0 || new_height == 0 || (new_width, new_height) == (width, height) { return image; }
+    image.crop_imm((width - new_width) / 2, (height - new_height) / 2, new_width, new_height)
 }
 
-fn img_cache() -> Option<PathBuf> {
+pub(crate) fn img_cache() -> Option<PathBuf> {
     #[cfg(unix)] { xdg_basedir::get_cache_home().ok().map(|cache_home| cache_home.join("magic-set-generator").join("img")) }
     #[cfg(not(unix))] { ProjectDirs::from("net", "Fenhl", "Magic Set Generator").map(|proj_dirs| proj_dirs.cache_dir().join("img")) }
 }
 
-fn normalized_image_name(card: &Card) -> String {
+pub(crate) fn normalized_image_name(card: &Card) -> String {
+    let primary_name = if card.is_alt() { Some(card.primary().to_string()) } else { None };
+    encode_image_name(&card.to_string(), primary_name.as_deref())
+}
+
+/// `%` is encoded along with the other special characters so that different card names never share an image file name, see “Image file names” in the readme.
+fn encode_image_name(card_name: &str, primary_name: Option<&str>) -> String {
+    let card_name = if let Some(primary_name) = primary_name { format!("{} ({})", primary_name, card_name) } else { card_name.to_owned() };
+    let mut image_name = String::with_capacity(card_name.len());
+    for c in card_name.chars() {
+        match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '%' => { image_name.push_str(&format!("%{:02X}", c as u32)); }
+            c if c.is_control() => {
+                let mut buf = [0; 4];
+                for byte in c.encode_utf8(&mut buf).bytes() {
+                    image_name.push_str(&format!("%{:02X}", byte));
+                }
+            }
+            c => { image_name.push(c); }
+        }
+    }
+    let trimmed_len = image_name.trim_end_matches(|c| c == '.' || c == ' ').len();
+    let trailing = image_name.split_off(trimmed_len);
+    for c in trailing.chars() {
+        image_name.push_str(&format!("%{:02X}", c as u32));
+    }
+    let device_name = image_name.split('.').next().expect("split returned no items").trim_end().to_uppercase();
+    if WINDOWS_RESERVED_NAMES.contains(&&device_name[..]) {
+        let first = image_name.remove(0);
+        image_name.insert_str(0, &format!("%{:02X}", first as u32));
+    }
+    image_name
+}
+
+fn legacy_image_name(card: &Card) -> String {
     let mut card_name = card.to_string();
     card_name.retain(|c| match c {
         ':' | '"' | '?' => false,
@@ -286,3 +832,48 @@ fn normalized_image_name(card: &Card) -> String {
     });
     card_name
 }
+
+pub(crate) fn image_names(card: &Card) -> Vec<String> {
+    let mut names = vec![normalized_image_name(card)];
+    let legacy_name = legacy_image_name(card);
+    if legacy_name != names[0] { names.push(legacy_name); }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_names_are_unchanged() {
+        assert_eq!(encode_image_name("Lightning Bolt", None), "Lightning Bolt");
+        assert_eq!(encode_image_name("Æther Vial", None), "Æther Vial");
+    }
+
+    #[test]
+    fn alternate_faces_are_prefixed() {
+        assert_eq!(encode_image_name("Insectile Aberration", Some("Delver of Secrets")), "Delver of Secrets (Insectile Aberration)");
+    }
+
+    #[test]
+    fn special_characters_are_encoded() {
+        assert_eq!(encode_image_name("Circle of Protection: Red", None), "Circle of Protection%3A Red");
+        assert_eq!(encode_image_name("Question Elemental?", None), "Question Elemental%3F");
+        assert_eq!(encode_image_name("Fire // Ice", None), "Fire %2F%2F Ice");
+        assert_eq!(encode_image_name("100%", None), "100%25");
+    }
+
+    #[test]
+    fn trailing_dots_and_spaces_are_encoded() {
+        assert_eq!(encode_image_name("B.F.M.", None), "B.F.M%2E");
+        assert_eq!(encode_image_name("Etc. ", None), "Etc%2E%20");
+    }
+
+    #[test]
+    fn reserved_names_are_encoded() {
+        assert_eq!(encode_image_name("CON", None), "%43ON");
+        assert_eq!(encode_image_name("Con", None), "%43on");
+        assert_eq!(encode_image_name("nul.txt", None), "%6Eul.txt");
+        assert_eq!(encode_image_name("Con Artist", None), "Con Artist");
+    }
+}
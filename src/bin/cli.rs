@@ -1,15 +1,26 @@
 #![deny(rust_2018_idioms, unused, unused_import_braces, unused_qualifications, warnings)]
 
 use {
-    std::io::{
-        prelude::*,
-        stderr
+    std::{
+        fs,
+        io::{
+            prelude::*,
+            stderr
+        }
     },
     async_std::task,
     gres::Task as _,
     msegen::{
         Run,
-        args::Args,
+        args::{
+            Args,
+            Output
+        },
+        cache::{
+            self,
+            CacheCommand
+        },
+        manifest,
         util::{
             Error,
             IoResultExt as _
@@ -55,6 +66,46 @@ fn main_inner() -> Result<(), Error> {
             println!("Magic Set Generator version {} ({})", env!("CARGO_PKG_VERSION"), &version::GIT_COMMIT_HASH[..7]);
             return Ok(());
         }
+        Args::Cache(args, command) => {
+            match command {
+                CacheCommand::List => for image in cache::list(&args)? {
+                    println!("{}", image.path.display());
+                },
+                CacheCommand::Size => {
+                    let images = cache::list(&args)?;
+                    let size = images.iter().map(|image| image.size).sum::<u64>();
+                    println!("{} images, {:.1} MiB", images.len(), size as f64 / (1024.0 * 1024.0));
+                }
+                CacheCommand::Prune { older_than, keep } => {
+                    let pruned = cache::prune(&client, &args, older_than, keep.as_deref())?;
+                    for image in &pruned {
+                        verbose_eprintln!(args, "[ ** ] deleted {}", image.path.display());
+                    }
+                    println!("deleted {} images", pruned.len());
+                }
+                CacheCommand::Verify => {
+                    let invalid = cache::verify(&args)?;
+                    for (image, msg) in &invalid {
+                        println!("{}: {}", image.path.display(), msg);
+                    }
+                    if !invalid.is_empty() {
+                        eprintln!("[ !! ] {} cached images could not be decoded, delete them to download them again", invalid.len());
+                    }
+                }
+                CacheCommand::Clear => {
+                    println!("deleted {} images", cache::clear(&args)?.len());
+                }
+            }
+            return Ok(());
+        }
+        Args::ImageManifest(args) => {
+            let skeleton = manifest::skeleton(&client, &args)?;
+            match args.output {
+                Output::File(ref path) => { fs::write(path, skeleton).at(path)?; }
+                Output::Stdout => { print!("{}", skeleton); }
+            }
+            return Ok(());
+        }
         Args::Regular(args) => args
     };
     let mut run = msegen::Run::new(client, args.clone());
@@ -75,8 +126,14 @@ fn main_inner() -> Result<(), Error> {
                     Run::LoadDb { updates_available: Some(true), .. } => { eprintln!("\r[ !! ] an update is available, install with `msegen --update`"); }
                     Run::LoadDb { updates_available: Some(false), .. } => { eprintln!("\r[ ok ] Magic Set Generator is up to date"); }
                     Run::NormalizeCardNames { .. } => { verbose_eprint!(args, "[....] normalizing card names"); }
-                    Run::CreateSetMetadata { ref cards, .. } => {
+                    Run::CreateSetMetadata { ref cards, ref corrected_names, ref unmatched_images, .. } => {
                         verbose_eprintln!(args, "\r[ ok ]");
+                        for (input_name, corrected_name) in corrected_names {
+                            eprintln!("[ ** ] no card named {:?} found, using {}", input_name, corrected_name);
+                        }
+                        for path in unmatched_images {
+                            eprintln!("[ !! ] no card found for image {}", path.display());
+                        }
                         if cards.is_empty() {
                             verbose_eprintln!(args, "[ !! ] no cards specified, generating empty set file");
                         }
@@ -100,14 +157,22 @@ fn main_inner() -> Result<(), Error> {
                         let progress = 4.min(5 * added_cards / total_cards);
                         verbose_eprint!(args, "[{}{}] adding cards to set file: {} of {}\r", "=".repeat(progress), ".".repeat(4 - progress), added_cards, total_cards);
                     }
-                    Run::GenerateStylesheetSettings { failed, .. } => {
+                    Run::GenerateStylesheetSettings { failed, ref missing_cards, .. } => {
+                        for msg in missing_cards {
+                            eprintln!("[ !! ] {}", msg);
+                        }
                         if failed > 0 {
                             eprintln!("[ ** ] {} cards failed. Run again with --verbose for a detailed error message", failed);
                         }
                     }
                     Run::WriteMain { .. } => { verbose_eprint!(args, "[....] adding images and saving\r[....]"); }
                     Run::CopyMain { .. } => { verbose_eprint!(args, "\r[=...]"); }
-                    Run::WriteSchemes { .. } => { verbose_eprint!(args, "\r[==..]"); }
+                    Run::WriteSchemes { ref art_handler, .. } => {
+                        for warning in art_handler.image_warnings() {
+                            eprintln!("\r[ !! ] {}", warning);
+                        }
+                        verbose_eprint!(args, "\r[==..]");
+                    }
                     Run::WriteVanguards { .. } => { verbose_eprint!(args, "\r[===.]"); }
                     _ => {}
                 }
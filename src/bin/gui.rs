@@ -57,6 +57,8 @@ struct JsonToMse {
     args: ArgsState,
     #[default(msegen::client().expect("failed to create HTTP client"))]
     client: Client,
+    /// Corrected and missing card names from the current or last run.
+    warnings: Vec<String>,
     update_progress: Arc<RwLock<UpdateProgress>>,
     #[default(Ok(button::State::default()))]
     run: Result<button::State, (Percent, String)>
@@ -97,8 +99,18 @@ impl Application for JsonToMse {
             Message::Generate(run) => {
                 let run = if let Some(run) = run {
                     self.run = Err((run.progress(), run.to_string()));
+                    if let Run::CreateSetMetadata { ref corrected_names, ref missing_cards, .. } = run {
+                        self.warnings = corrected_names.iter()
+                            .map(|(input_name, corrected_name)| format!("no card named {:?} found, using {}", input_name, corrected_name))
+                            .chain(missing_cards.iter().map(|msg| format!("skipped card: {}", msg)))
+                            .collect();
+                    }
+                    if let Run::WriteSchemes { ref art_handler, .. } = run {
+                        self.warnings.extend(art_handler.image_warnings().iter().cloned());
+                    }
                     run
                 } else {
+                    self.warnings = Vec::default();
                     Run::new(self.client.clone(), self.args.args.clone())
                 };
                 async {
@@ -116,6 +128,9 @@ impl Application for JsonToMse {
         let mut col = Column::new()
             .push(Text::new(format!("{}", self.update_progress.read())))
             .push(self.args.view());
+        for warning in &self.warnings {
+            col = col.push(Text::new(warning));
+        }
         match self.run {
             Ok(ref mut start_button) => {
                 col = col.push(
@@ -161,7 +176,9 @@ impl ArgsState {
             ArgsMessage::NewCardNameChange(new_card_name) => { self.new_card_name = new_card_name; }
             ArgsMessage::AddCard => {
                 let new_card_name = mem::take(&mut self.new_card_name);
-                if self.args.cards.insert(new_card_name) {
+                let quantity = self.args.cards.entry(new_card_name).or_default();
+                *quantity += 1;
+                if *quantity == 1 {
                     if let Some(ref mut btns) = self.card_delete_buttons {
                         btns.push(button::State::default());
                     }
@@ -173,7 +190,9 @@ impl ArgsState {
                 match lore_seeker::resolve_query(None, &query) { //TODO async, allow changing Lore Seeker hostname
                     Ok((_, cards)) => {
                         self.query_error = None;
-                        self.args.cards.extend(cards.into_iter().map(|(card_name, _)| card_name));
+                        for (card_name, _) in cards {
+                            self.args.cards.entry(card_name).or_insert(1);
+                        }
                         if let Some(ref mut btns) = self.card_delete_buttons {
                             btns.resize_with(self.args.cards.len(), button::State::default);
                         }
@@ -186,7 +205,7 @@ impl ArgsState {
             } else {
                 self.args.output = Output::File(PathBuf::from(new_path));
             },
-            ArgsMessage::RemoveCard(card_name) => if self.args.cards.remove(&card_name) {
+            ArgsMessage::RemoveCard(card_name) => if self.args.cards.remove(&card_name).is_some() {
                 if let Some(ref mut btns) = self.card_delete_buttons {
                     btns.pop();
                 }
@@ -209,7 +228,7 @@ impl ArgsState {
                 .push(Button::new(&mut self.show_hide_cards_button, Text::new(if self.card_delete_buttons.is_some() { "Hide" } else { "Show" })).on_press(Message::Args(ArgsMessage::ShowHideCards)))
             );
         if let Some(ref mut del_btns) = self.card_delete_buttons {
-            for (card_name, btn) in self.args.cards.iter().cloned().sorted().zip(del_btns) {
+            for (card_name, btn) in self.args.cards.keys().cloned().sorted().zip(del_btns) {
                 col = col.push(Row::new().push(Text::new(card_name.clone())).push(Button::new(btn, Text::new("Remove")).on_press(Message::Args(ArgsMessage::RemoveCard(card_name)))));
             }
         }
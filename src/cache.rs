@@ -0,0 +1,137 @@
+//! The `msegen cache` commands, for managing downloaded card images.
+
+use {
+    std::{
+        collections::HashSet,
+        ffi::OsStr,
+        fs,
+        path::{
+            Path,
+            PathBuf
+        },
+        time::{
+            Duration,
+            SystemTime
+        }
+    },
+    image::io::Reader as ImageReader,
+    reqwest::blocking::Client,
+    crate::{
+        args::ArgsRegular,
+        art::{
+            self,
+            IMAGE_EXTENSIONS,
+            ImageMetadata
+        },
+        generated_cards,
+        load_db,
+        requested_cards,
+        util::{
+            Error,
+            IoResultExt as _,
+            card_faces
+        }
+    }
+};
+
+#[derive(Debug, Clone)]
+pub enum CacheCommand {
+    List,
+    Size,
+    /// An image is deleted if it matches either criterion.
+    Prune {
+        older_than: Option<Duration>,
+        keep: Option<PathBuf>
+    },
+    Verify,
+    Clear
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedImage {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime
+}
+
+/// Outside the OS-specific cache directory, only images with a metadata file are considered cached, since directories like `--images` also contain images added by the user.
+pub fn list(args: &ArgsRegular) -> Result<Vec<CachedImage>, Error> {
+    let img_cache = art::img_cache();
+    let mut images = Vec::default();
+    for img_dir in art::download_dirs(args) {
+        if !img_dir.exists() { continue; }
+        for entry in fs::read_dir(&img_dir).at(&img_dir)? {
+            let path = entry.at(&img_dir)?.path();
+            if !path.extension().and_then(OsStr::to_str).map_or(false, |ext| IMAGE_EXTENSIONS.contains(&ext)) { continue; }
+            let in_img_cache = img_cache.as_ref().map_or(false, |img_cache| path.starts_with(img_cache));
+            if !in_img_cache && !ImageMetadata::sidecar_path(&path).exists() { continue; }
+            let metadata = fs::metadata(&path).at(&path)?;
+            images.push(CachedImage {
+                size: metadata.len(),
+                modified: metadata.modified().at(&path)?,
+                path
+            });
+        }
+    }
+    images.sort_by(|img1, img2| img1.path.cmp(&img2.path));
+    Ok(images)
+}
+
+pub fn prune(client: &Client, args: &ArgsRegular, older_than: Option<Duration>, keep: Option<&Path>) -> Result<Vec<CachedImage>, Error> {
+    let keep_names = if let Some(keep) = keep { Some(image_names(client, args, keep)?) } else { None };
+    let now = SystemTime::now();
+    let mut pruned = Vec::default();
+    for image in list(args)? {
+        let too_old = older_than.map_or(false, |older_than| now.duration_since(image.modified).map_or(false, |age| age > older_than));
+        let not_kept = keep_names.as_ref().map_or(false, |keep_names| !image.path.file_stem().and_then(OsStr::to_str).map_or(false, |stem| keep_names.contains(stem)));
+        if too_old || not_kept {
+            remove(&image)?;
+            pruned.push(image);
+        }
+    }
+    Ok(pruned)
+}
+
+/// The image format is detected from the file contents rather than the extension, since older versions saved some JPEG images with a `.png` extension.
+pub fn verify(args: &ArgsRegular) -> Result<Vec<(CachedImage, String)>, Error> {
+    let mut invalid = Vec::default();
+    for img in list(args)? {
+        let result = ImageReader::open(&img.path).at(&img.path)?
+            .with_guessed_format().at(&img.path)?
+            .decode();
+        if let Err(e) = result {
+            invalid.push((img, e.to_string()));
+        }
+    }
+    Ok(invalid)
+}
+
+pub fn clear(args: &ArgsRegular) -> Result<Vec<CachedImage>, Error> {
+    let images = list(args)?;
+    for image in &images {
+        remove(image)?;
+    }
+    Ok(images)
+}
+
+fn remove(image: &CachedImage) -> Result<(), Error> {
+    fs::remove_file(&image.path).at(&image.path)?;
+    let sidecar_path = ImageMetadata::sidecar_path(&image.path);
+    if sidecar_path.exists() {
+        fs::remove_file(&sidecar_path).at(sidecar_path)?;
+    }
+    Ok(())
+}
+
+/// The file is read like `--input`, so it can also contain queries, decklists, and `!all`.
+fn image_names(client: &Client, args: &ArgsRegular, card_list: &Path) -> Result<HashSet<String>, Error> {
+    let db = load_db(args)?;
+    let cards = requested_cards(client, &args.card_list(card_list)?, &db)?;
+    // refuse to delete the entire cache because of a file that couldn't be read as intended
+    if cards.is_empty() { return Err(Error::Args(format!("no cards found in {}", card_list.display()))); }
+    Ok(cards.into_iter()
+        .flat_map(generated_cards)
+        .flat_map(|card| card_faces(&card))
+        .flat_map(|face| art::image_names(&face))
+        .collect())
+}
@@ -0,0 +1,211 @@
+//! Support for deck files exported by other programs, see `--input`.
+
+use {
+    lazy_static::lazy_static,
+    regex::Regex,
+    reqwest::blocking::Client,
+    url::Url,
+    xml::{
+        attribute::OwnedAttribute,
+        reader::{
+            EventReader,
+            XmlEvent
+        }
+    },
+    crate::{
+        args::Printing,
+        util::Error
+    }
+};
+
+const ARENA_SECTIONS: [&str; 5] = ["Commander", "Companion", "Deck", "Maybeboard", "Sideboard"];
+
+lazy_static! {
+    static ref QUANTITY_REGEX: Regex = Regex::new("^([0-9]{1,3})[Xx]? +(.+)$").expect("failed to build quantity regex");
+    static ref TAPPEDOUT_MARKERS_REGEX: Regex = Regex::new("( +\\*[A-Za-z0-9]+\\*)+$").expect("failed to build tappedout markers regex");
+    static ref ARENA_LINE_REGEX: Regex = Regex::new("^(.+) \\(([0-9A-Za-z]+)\\) ([0-9A-Za-z★-]+)$").expect("failed to build MTG Arena line regex");
+    static ref PRINTING_REGEX: Regex = Regex::new("^(.+?) \\(([0-9A-Za-z]+)\\)(?: ([0-9A-Za-z★-]+))?$").expect("failed to build printing regex");
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Plain,
+    /// Apart from the section headers and the `About` section, this is a subset of the plain format, so plain files with printings are also detected as this.
+    Arena,
+    Mtgo,
+    Cockatrice
+}
+
+impl Format {
+    pub(crate) fn detect(text: &str) -> Result<Format, Error> {
+        let text = text.trim_start_matches('\u{feff}').trim_start();
+        if text.starts_with('<') {
+            for event in EventReader::from_str(text) {
+                if let XmlEvent::StartElement { name, .. } = event? {
+                    return match &name.local_name[..] {
+                        "Deck" => Ok(Format::Mtgo),
+                        "cockatrice_deck" => Ok(Format::Cockatrice),
+                        root => Err(Error::Args(format!("unknown XML deck format with root element {}", root)))
+                    };
+                }
+            }
+            Err(Error::Args(format!("empty XML deck file")))
+        } else if text.lines().map(str::trim).any(|line| ARENA_SECTIONS.contains(&line) || ARENA_LINE_REGEX.is_match(line)) {
+            Ok(Format::Arena)
+        } else {
+            Ok(Format::Plain)
+        }
+    }
+}
+
+/// Splits a decklist line like `4 Lightning Bolt` or `4x Lightning Bolt (M10) 146` into quantity, card name, and printing.
+///
+/// Lines without a quantity are parsed as a single copy. A quantity of 0 is an error.
+pub(crate) fn card_line(line: &str) -> Result<(usize, &str, Option<Printing>), Error> {
+    let (quantity, card) = if let Some(captures) = QUANTITY_REGEX.captures(line) {
+        (quantity(&captures[1])?, captures.get(2).expect("missing card in quantity regex").as_str())
+    } else {
+        (1, line)
+    };
+    Ok(if let Some(captures) = PRINTING_REGEX.captures(card) {
+        (quantity, captures.get(1).expect("missing card in printing regex").as_str(), Some(Printing {
+            set_code: captures[2].to_uppercase(),
+            collector_number: captures.get(3).map(|collector_number| collector_number.as_str().to_owned())
+        }))
+    } else {
+        (quantity, card, None)
+    })
+}
+
+/// The returned lines may also include comments or other lines from a plain file.
+pub(crate) fn arena_card_lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::default();
+    let mut in_about_section = false;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            in_about_section = false;
+        } else if line == "About" {
+            in_about_section = true;
+        } else if ARENA_SECTIONS.contains(&line) {
+            in_about_section = false;
+        } else if !in_about_section {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/// Includes the sideboard.
+pub(crate) fn mtgo_cards(text: &str) -> Result<Vec<(String, usize)>, Error> {
+    let mut cards = Vec::default();
+    for event in EventReader::from_str(text.trim_start_matches('\u{feff}')) {
+        if let XmlEvent::StartElement { name, attributes, .. } = event? {
+            if name.local_name == "Cards" {
+                cards.push((attribute(&attributes, "Name")?, quantity(&attribute(&attributes, "Quantity")?)?));
+            }
+        }
+    }
+    Ok(cards)
+}
+
+/// Includes the sideboard but not the tokens.
+pub(crate) fn cockatrice_cards(text: &str) -> Result<Vec<(String, usize)>, Error> {
+    let mut cards = Vec::default();
+    let mut zone = None;
+    for event in EventReader::from_str(text.trim_start_matches('\u{feff}')) {
+        match event? {
+            XmlEvent::StartElement { name, attributes, .. } => match &name.local_name[..] {
+                "zone" => { zone = Some(attribute(&attributes, "name")?); }
+                "card" => if zone.as_deref() != Some("tokens") {
+                    cards.push((attribute(&attributes, "name")?, quantity(&attribute(&attributes, "number")?)?));
+                },
+                _ => {}
+            },
+            XmlEvent::EndElement { name } => if name.local_name == "zone" {
+                zone = None;
+            },
+            _ => {}
+        }
+    }
+    Ok(cards)
+}
+
+/// Downloads the given deck from tappedout.net (or a compatible site at `base_url`) and returns its card names and quantities, including the sideboard.
+pub(crate) fn tappedout_cards(client: &Client, base_url: &Url, deck_id: &str) -> Result<Vec<(String, usize)>, Error> {
+    let mut url = base_url.clone();
+    url.path_segments_mut()
+        .map_err(|()| Error::Args(format!("invalid tappedout URL: {}", base_url)))?
+        .pop_if_empty()
+        .push("mtg-decks")
+        .push(deck_id)
+        .push(""); // tappedout deck URLs end with a slash
+    url.query_pairs_mut().append_pair("fmt", "txt");
+    let text = client.get(url.as_str())
+        .send()?
+        .error_for_status()?
+        .text()?;
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.ends_with(':')) // skip section headers like “Sideboard:”
+        .map(|line| {
+            let (quantity, card_name, _) = card_line(line)?;
+            Ok((TAPPEDOUT_MARKERS_REGEX.replace(card_name, "").into_owned(), quantity)) // remove markers like “*CMDR*” or “*F*”
+        })
+        .collect()
+}
+
+fn attribute(attributes: &[OwnedAttribute], key: &str) -> Result<String, Error> {
+    attributes.iter()
+        .find(|attr| attr.name.local_name == key)
+        .map(|attr| attr.value.clone())
+        .ok_or_else(|| Error::Args(format!("missing {} attribute in deck file", key)))
+}
+
+fn quantity(text: &str) -> Result<usize, Error> {
+    match text.parse() {
+        Ok(0) => Err(Error::Args(format!("card quantity must not be 0: {:?}", text))),
+        Ok(quantity) => Ok(quantity),
+        Err(_) => Err(Error::Args(format!("invalid card quantity in deck file: {:?}", text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printing(set_code: &str, collector_number: Option<&str>) -> Option<Printing> {
+        Some(Printing { set_code: set_code.into(), collector_number: collector_number.map(String::from) })
+    }
+
+    #[test]
+    fn card_line_quantities() {
+        assert_eq!(card_line("Lightning Bolt").unwrap(), (1, "Lightning Bolt", None));
+        assert_eq!(card_line("4 Lightning Bolt").unwrap(), (4, "Lightning Bolt", None));
+        assert_eq!(card_line("4x Lightning Bolt").unwrap(), (4, "Lightning Bolt", None));
+        assert_eq!(card_line("1 Fire // Ice").unwrap(), (1, "Fire // Ice", None));
+        assert!(card_line("0 Lightning Bolt").is_err());
+    }
+
+    #[test]
+    fn card_line_printings() {
+        assert_eq!(card_line("Lightning Bolt (m10)").unwrap(), (1, "Lightning Bolt", printing("M10", None)));
+        assert_eq!(card_line("4x Lightning Bolt (M10) 146").unwrap(), (4, "Lightning Bolt", printing("M10", Some("146"))));
+        assert_eq!(card_line("2 Fire // Ice (MH2) 290").unwrap(), (2, "Fire // Ice", printing("MH2", Some("290"))));
+    }
+
+    #[test]
+    fn detect_formats() {
+        assert_eq!(Format::detect("4 Lightning Bolt\n2 Fire // Ice\n").unwrap(), Format::Plain);
+        assert_eq!(Format::detect("Deck\n4 Lightning Bolt\n").unwrap(), Format::Arena);
+        assert_eq!(Format::detect("4 Lightning Bolt (M10) 146\n").unwrap(), Format::Arena);
+        assert_eq!(Format::detect("\u{feff}<?xml version=\"1.0\"?>\n<Deck><Cards Quantity=\"4\" Name=\"Lightning Bolt\" /></Deck>").unwrap(), Format::Mtgo);
+        assert_eq!(Format::detect("<?xml version=\"1.0\"?>\n<cockatrice_deck version=\"1\"></cockatrice_deck>").unwrap(), Format::Cockatrice);
+        assert!(Format::detect("<html></html>").is_err());
+    }
+
+    #[test]
+    fn arena_card_lines_skip_sections_and_metadata() {
+        let text = "About\nName My Deck\n\nCommander\n1 Gisela, Blade of Goldnight (V17) 5\n\nDeck\n4 Lightning Bolt (M10) 146\n\nSideboard\n2 Fire // Ice (MH2) 290\n";
+        assert_eq!(arena_card_lines(text), vec!["1 Gisela, Blade of Goldnight (V17) 5", "4 Lightning Bolt (M10) 146", "2 Fire // Ice (MH2) 290"]);
+    }
+}
@@ -2,21 +2,33 @@
 
 pub mod args;
 pub mod art;
+pub mod cache;
+mod decklist;
 pub mod github;
+pub mod manifest;
 pub mod mse;
+mod names;
+pub mod overrides;
+mod query;
+mod scryfall;
 pub mod util;
 pub mod version;
 
 use {
     std::{
-        collections::BTreeSet,
+        collections::btree_map::{
+            BTreeMap,
+            Entry
+        },
         fmt,
         fs::File,
         io::{
             self,
             Cursor,
             stdout
-        }
+        },
+        iter,
+        path::PathBuf
     },
     async_trait::async_trait,
     gitdir::Host as _,
@@ -41,11 +53,18 @@ use {
             ArgsRegular,
             Output
         },
-        art::ArtHandler,
+        art::{
+            self,
+            ArtHandler
+        },
         mse::{
             DataFile,
             MseGame
         },
+        names::{
+            self,
+            NameIndex
+        },
         util::{
             Error,
             IoResultExt as _
@@ -86,18 +105,21 @@ pub enum Run {
         args: ArgsRegular,
         db: Db,
         queries: Vec<String>,
-        cards: BTreeSet<String>
+        cards: BTreeMap<String, usize>
     },
     NormalizeCardNames {
         client: Client,
         args: ArgsRegular,
         db: Db,
-        cards: BTreeSet<String>
+        cards: BTreeMap<String, usize>
     },
     CreateSetMetadata {
         client: Client,
         args: ArgsRegular,
-        cards: BTreeSet<Card>
+        cards: BTreeMap<Card, usize>,
+        corrected_names: Vec<(String, String)>,
+        missing_cards: Vec<String>,
+        unmatched_images: Vec<PathBuf>
     },
     AddNextCard {
         client: Client,
@@ -106,6 +128,7 @@ pub enum Run {
         added_cards: usize,
         failed: usize,
         error: Option<(String, String, String)>,
+        missing_cards: Vec<String>,
         art_handler: ArtHandler,
         set_file: DataFile,
         schemes_set_file: DataFile,
@@ -114,6 +137,7 @@ pub enum Run {
     GenerateStylesheetSettings {
         args: ArgsRegular,
         failed: usize,
+        missing_cards: Vec<String>,
         art_handler: ArtHandler,
         set_file: DataFile,
         schemes_set_file: DataFile,
@@ -227,17 +251,7 @@ impl Task<Result<(), Error>> for Run {
                 client, args
             }),
             Run::LoadDb { client, args, .. } => Err(Run::ResolveQueries {
-                db: if let Some(ref db_path) = args.database {
-                    if db_path.is_dir() {
-                        task_try!(Db::from_sets_dir(db_path, args.verbose))
-                    } else {
-                        task_try!(Db::from_mtg_json(task_try!(serde_json::from_reader(task_try!(File::open(db_path).at(db_path)))), args.verbose))
-                    }
-                } else if args.offline {
-                    task_try!(Db::from_sets_dir(task_try!(gitdir::GitHub.repo("fenhl/lore-seeker").master()).join("data").join("sets"), args.verbose))
-                } else {
-                    task_try!(Db::download(args.verbose))
-                },
+                db: task_try!(load_db(&args)),
                 cards: args.cards.clone(),
                 queries: args.queries.iter().cloned().collect(),
                 client, args
@@ -245,11 +259,16 @@ impl Task<Result<(), Error>> for Run {
             Run::ResolveQueries { client, args, db, mut queries, mut cards } => if args.all_command {
                 Err(Run::CreateSetMetadata {
                     client, args,
-                    cards: db.into_iter().collect()
+                    cards: db.into_iter().map(|card| (card, 1)).collect(),
+                    corrected_names: Vec::default(),
+                    missing_cards: Vec::default(),
+                    unmatched_images: Vec::default()
                 })
             } else {
                 if let Some(query) = queries.pop() {
-                    cards.extend(task_try!(lore_seeker::resolve_query(args.lore_seeker_hostname.as_deref(), &query)).1.into_iter().map(|(card_name, _)| card_name)); //TODO async
+                    for card_name in task_try!(query::card_names(&client, &args, &db, &query)) {
+                        cards.entry(card_name).or_insert(1);
+                    }
                 }
                 Err(if queries.is_empty() {
                     Run::NormalizeCardNames { client, args, db, cards }
@@ -257,43 +276,96 @@ impl Task<Result<(), Error>> for Run {
                     Run::ResolveQueries { client, args, db, queries, cards }
                 })
             },
-            Run::NormalizeCardNames { client, args, db, cards } => Err(Run::CreateSetMetadata {
-                cards: if args.all_command { //TODO remove if still not required after adding !tappedout
-                    db.into_iter().collect()
-                } else {
-                    task_try!(cards.into_iter()
-                        //TODO also read card names from args.decklists
-                        .map(|card_name| card_name.replace('’', "'"))
-                        .map(|card_name| match SPLIT_CARD_REGEX.captures(&card_name) {
-                            Some(captures) => captures[1].to_owned(),
-                            None => card_name.to_owned()
-                        })
-                        .map(|card_name| db.card(&card_name).ok_or_else(|| Error::CardNotFound(card_name)))
-                        .collect::<Result<BTreeSet<_>, _>>()
-                    )
-                }.into_iter()
-                    .flat_map(|card| if let Layout::Meld { top, bottom, .. } = card.layout() {
-                        vec![top, bottom]
+            Run::NormalizeCardNames { client, mut args, db, mut cards } => {
+                for deck_id in &args.decklists {
+                    for (card_name, quantity) in task_try!(decklist::tappedout_cards(&client, &args.tappedout_url, deck_id)) {
+                        *cards.entry(card_name).or_default() += quantity;
+                    }
+                }
+                let mut printings = BTreeMap::default();
+                let mut name_index = None;
+                let mut corrected_names = Vec::default();
+                let mut found_cards = Vec::default();
+                let mut missing_cards = Vec::default();
+                for (input_name, quantity) in cards {
+                    let card_name = input_name.replace('’', "'");
+                    let card_name = match SPLIT_CARD_REGEX.captures(&card_name) {
+                        Some(captures) => captures[1].to_owned(),
+                        None => card_name.to_owned()
+                    };
+                    let card = if let Some(card) = db.card(&card_name) {
+                        card
                     } else {
-                        vec![card.primary()]
-                    })
-                    .collect(),
-                client, args
-            }),
-            Run::CreateSetMetadata { client, args, cards } => Err(Run::AddNextCard {
-                added_cards: 0,
-                failed: 0,
-                error: None,
-                art_handler: ArtHandler::new(&args, client.clone()),
-                set_file: DataFile::new(&args, cards.len()),
-                schemes_set_file: DataFile::new_schemes(&args, cards.len()),
-                vanguards_set_file: DataFile::new_vanguards(&args, cards.len()),
-                client, args,
-                cards: cards.into_iter().collect()
-            }),
-            Run::AddNextCard { client, args, mut cards, added_cards, failed, mut art_handler, mut set_file, mut schemes_set_file, mut vanguards_set_file, .. } => {
+                        if name_index.is_none() {
+                            name_index = Some(task_try!(NameIndex::new(&db, names::mtg_json_path(&args).as_deref())));
+                        }
+                        let name_index = name_index.as_ref().expect("name index was just created");
+                        let result = if let Some(db_name) = name_index.resolve(&input_name).or_else(|| name_index.resolve(&card_name)).or_else(|| name_index.resolve_online(&client, &args, &card_name)) {
+                            Ok(db_name)
+                        } else {
+                            match name_index.fuzzy_match(&card_name) {
+                                Ok((face_name, db_name)) => {
+                                    corrected_names.push((input_name.clone(), face_name.to_owned()));
+                                    Ok(db_name)
+                                }
+                                Err(suggestions) => Err(Error::CardNotFound(card_name, suggestions))
+                            }
+                        }.and_then(|db_name| db.card(db_name).ok_or_else(|| Error::CardNotFound(db_name.to_owned(), Vec::default())));
+                        match result {
+                            Ok(card) => card,
+                            Err(e) => if args.strict {
+                                return Ok(Err(e));
+                            } else {
+                                missing_cards.push(e.to_string());
+                                continue;
+                            }
+                        }
+                    };
+                    if let Some(printing) = args.printings.get(&input_name) {
+                        // re-key by the primary face's name, which is what the art handler looks printings up by
+                        let primary = card.primary();
+                        match printings.entry(primary.to_string()) {
+                            Entry::Occupied(entry) => if entry.get() != printing {
+                                return Ok(Err(Error::Args(format!("conflicting printings selected for {}: {} and {}", primary, entry.get(), printing))));
+                            },
+                            Entry::Vacant(entry) => { entry.insert(printing.clone()); }
+                        }
+                    }
+                    found_cards.push((card, quantity));
+                }
+                args.printings = printings;
+                let (image_cards, unmatched_images) = task_try!(art::cards_from_image_dirs(&db, &args.image_inputs));
+                let mut normalized_cards = BTreeMap::<Card, usize>::default();
+                for (card, quantity) in found_cards {
+                    for card in generated_cards(card) {
+                        *normalized_cards.entry(card).or_default() += quantity;
+                    }
+                }
+                for card in image_cards {
+                    for card in generated_cards(card) {
+                        normalized_cards.entry(card).or_insert(1);
+                    }
+                }
+                Err(Run::CreateSetMetadata { client, args, cards: normalized_cards, corrected_names, missing_cards, unmatched_images })
+            }
+            Run::CreateSetMetadata { client, args, cards, missing_cards, .. } => {
+                let cards = cards.into_iter()
+                    .flat_map(|(card, quantity)| iter::repeat(card).take(if args.copies { quantity } else { 1 }))
+                    .collect::<Vec<_>>();
+                Err(Run::AddNextCard {
+                    added_cards: 0,
+                    failed: missing_cards.len(),
+                    error: None,
+                    art_handler: ArtHandler::new(&args, client.clone()),
+                    set_file: DataFile::new(&args, cards.len()),
+                    schemes_set_file: DataFile::new_schemes(&args, cards.len()),
+                    vanguards_set_file: DataFile::new_vanguards(&args, cards.len()),
+                    client, args, cards, missing_cards
+                })
+            }
+            Run::AddNextCard { client, args, mut cards, added_cards, failed, missing_cards, mut art_handler, mut set_file, mut schemes_set_file, mut vanguards_set_file, .. } => {
                 if cards.is_empty() {
-                    Err(Run::GenerateStylesheetSettings { args, failed, art_handler, set_file, schemes_set_file, vanguards_set_file })
+                    Err(Run::GenerateStylesheetSettings { args, failed, missing_cards, art_handler, set_file, schemes_set_file, vanguards_set_file })
                 } else {
                     let card = cards.remove(0);
                     let result = if card.type_line() >= CardType::Scheme {
@@ -312,7 +384,7 @@ impl Task<Result<(), Error>> for Run {
                         set_file.add_card(&card, MseGame::Magic, &args, &mut art_handler)
                     };
                     Err(Run::AddNextCard {
-                        client, args, cards, art_handler, set_file, schemes_set_file, vanguards_set_file,
+                        client, args, cards, missing_cards, art_handler, set_file, schemes_set_file, vanguards_set_file,
                         added_cards: added_cards + 1,
                         failed: if result.is_ok() { failed } else { failed + 1 },
                         error: result.err().map(|e| (card.to_string(), format!("{:?}", e), e.to_string()))
@@ -360,6 +432,47 @@ impl Task<Result<(), Error>> for Run {
     }
 }
 
+pub(crate) fn load_db(args: &ArgsRegular) -> Result<Db, Error> {
+    Ok(if let Some(ref db_path) = args.database {
+        if db_path.is_dir() {
+            Db::from_sets_dir(db_path, args.verbose)?
+        } else {
+            Db::from_mtg_json(serde_json::from_reader(File::open(db_path).at(db_path)?)?, args.verbose)?
+        }
+    } else if args.offline {
+        Db::from_sets_dir(gitdir::GitHub.repo("fenhl/lore-seeker").master()?.join("data").join("sets"), args.verbose)?
+    } else {
+        Db::download(args.verbose)?
+    })
+}
+
+/// Unlike in a regular run, card names which aren't found are skipped instead of corrected.
+pub(crate) fn requested_cards(client: &Client, args: &ArgsRegular, db: &Db) -> Result<Vec<Card>, Error> {
+    let mut cards = if args.all_command {
+        db.clone().into_iter().collect()
+    } else {
+        let mut card_names = args.cards.keys().cloned().collect::<Vec<_>>();
+        for query in &args.queries {
+            card_names.extend(query::card_names(client, args, db, query)?);
+        }
+        for deck_id in &args.decklists {
+            card_names.extend(decklist::tappedout_cards(client, &args.tappedout_url, deck_id)?.into_iter().map(|(card_name, _)| card_name));
+        }
+        names::lookup_cards(client, db, args, card_names)?
+    };
+    cards.extend(art::cards_from_image_dirs(db, &args.image_inputs)?.0);
+    Ok(cards)
+}
+
+/// The cards to add to the set file for the given card: both front faces for meld cards, or the primary face otherwise.
+pub(crate) fn generated_cards(card: Card) -> Vec<Card> {
+    if let Layout::Meld { top, bottom, .. } = card.layout() {
+        vec![top, bottom]
+    } else {
+        vec![card.primary()]
+    }
+}
+
 pub fn client() -> Result<Client, Error> {
     Ok(Client::builder()
         .default_headers({
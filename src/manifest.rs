@@ -0,0 +1,76 @@
+//! Explicit mappings from cards to their artwork, see `--image-manifest`.
+
+use {
+    std::{
+        collections::BTreeMap,
+        path::{
+            Path,
+            PathBuf
+        }
+    },
+    mtg::card::Card,
+    reqwest::blocking::Client,
+    serde::{
+        Deserialize,
+        Serialize
+    },
+    url::Url,
+    crate::{
+        args::ArgsRegular,
+        generated_cards,
+        load_db,
+        names,
+        requested_cards,
+        util::{
+            Error,
+            card_faces,
+            full_card_name,
+            load_card_map
+        }
+    }
+};
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct ManifestEntry {
+    /// Relative to the manifest file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Used if `path` is missing or doesn't exist.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>
+}
+
+/// Keyed by normalized card name.
+#[derive(Debug, Default, Clone)]
+pub struct ImageManifest(BTreeMap<String, ManifestEntry>);
+
+impl ImageManifest {
+    /// Entries with neither a path nor a URL are ignored, so skeleton entries don't prevent other image sources from being used.
+    pub(crate) fn load(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut entries = load_card_map(path.as_ref(), |entry: &mut ManifestEntry, base_dir| {
+            entry.path = entry.path.take().map(|img_path| base_dir.join(img_path));
+        })?;
+        entries.retain(|_, entry| entry.path.is_some() || entry.url.is_some());
+        self.0.extend(entries);
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, card: &Card) -> Option<&ManifestEntry> {
+        self.0.get(&names::normalize(&card.to_string()))
+            .or_else(|| self.0.get(&names::normalize(&full_card_name(card))))
+    }
+}
+
+/// Card names which aren't found are skipped.
+pub fn skeleton(client: &Client, args: &ArgsRegular) -> Result<String, Error> {
+    let db = load_db(args)?;
+    let entries = requested_cards(client, args, &db)?
+        .into_iter()
+        .flat_map(generated_cards)
+        .flat_map(|card| card_faces(&card))
+        .map(|face| (face.to_string(), ManifestEntry::default()))
+        .collect::<BTreeMap<_, _>>();
+    Ok(toml::to_string(&entries)?)
+}
@@ -52,6 +52,8 @@ use {
     }
 };
 
+const SET_SYMBOL_FILENAME: &str = "symbol1";
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MseGame {
     Magic,
@@ -126,19 +128,29 @@ impl DataFile {
         let mut set_info = DataFile::from_iter(vec![
             ("title", Data::from(title)),
             ("copyright", Data::from(&args.copyright[..])),
-            ("description", Data::from(format!(
-                "{} automatically generated using Magic Set Generator version {} ({}).",
-                if num_cards == 1 { "This card was" } else { "These cards were" },
-                env!("CARGO_PKG_VERSION"),
-                &version::GIT_COMMIT_HASH[..7]
-            ))),
+            ("description", Data::from(if let Some(ref description) = args.set_description {
+                // `{count}` is filled in by `write_to` since cards may still be skipped
+                description
+                    .replace("{version}", env!("CARGO_PKG_VERSION"))
+                    .replace("{commit}", &version::GIT_COMMIT_HASH[..7])
+            } else {
+                format!(
+                    "{} automatically generated using Magic Set Generator version {} ({}).",
+                    if num_cards == 1 { "This card was" } else { "These cards were" },
+                    env!("CARGO_PKG_VERSION"),
+                    &version::GIT_COMMIT_HASH[..7]
+                )
+            })),
             ("set code", Data::from(&args.set_code[..])),
-            ("set language", Data::from("EN")),
+            ("set language", Data::from(&args.set_language[..])),
             ("mark errors", Data::from("no")),
             ("automatic reminder text", Data::from(String::default())),
             ("automatic card numbers", Data::from(if args.auto_card_numbers { "yes" } else { "no" })),
             ("mana cost sorting", Data::from("unsorted"))
         ]);
+        if args.set_symbol.is_some() {
+            set_info.push("symbol", SET_SYMBOL_FILENAME);
+        }
         if args.border_color != (Color { r: 0, g: 0, b: 0, a: 1.0 }) {
             let Color { r, g, b, .. } = args.border_color;
             set_info.push("border color", format!("rgb({}, {}, {})", r, g, b));
@@ -155,15 +167,15 @@ impl DataFile {
     }
 
     pub fn new(args: &ArgsRegular, num_cards: usize) -> DataFile {
-        DataFile::new_inner(args, num_cards, "magic", "MTG JSON card import")
+        DataFile::new_inner(args, num_cards, "magic", &args.set_title)
     }
 
     pub fn new_schemes(args: &ArgsRegular, num_cards: usize) -> DataFile {
-        DataFile::new_inner(args, num_cards, "archenemy", "MTG JSON card import: Archenemy schemes")
+        DataFile::new_inner(args, num_cards, "archenemy", &format!("{}: Archenemy schemes", args.set_title))
     }
 
     pub fn new_vanguards(args: &ArgsRegular, num_cards: usize) -> DataFile {
-        DataFile::new_inner(args, num_cards, "vanguard", "MTG JSON card import: Vanguard avatars")
+        DataFile::new_inner(args, num_cards, "vanguard", &format!("{}: Vanguard avatars", args.set_title))
     }
 
     pub fn add_card(&mut self, card: &Card, mse_game: MseGame, args: &ArgsRegular, art_handler: &mut ArtHandler) -> Result<(), Error> {
@@ -180,6 +192,7 @@ impl DataFile {
 
     fn from_card(card: &Card, mse_game: MseGame, args: &ArgsRegular, art_handler: &mut ArtHandler) -> DataFile {
         let alt = card.is_alt();
+        let overrides = args.overrides.get(card);
         let mut result = DataFile::default();
 
         macro_rules! push_alt {
@@ -219,10 +232,15 @@ impl DataFile {
             push_alt!("casting cost", cost_to_mse(mana_cost));
         }
         // image
-        if let Some(image) = art_handler.register_image_for(card) {
+        let image = if let Some(image_path) = overrides.and_then(|overrides| overrides.image.as_ref()) {
+            art_handler.register_image_path(card, image_path.clone())
+        } else {
+            art_handler.register_image_for(card)
+        };
+        if let Some(image) = image {
             let image = image.lock();
             push_alt!("image", format!("image{}", image.id));
-            if let Some(ref artist) = image.artist {
+            if let Some(ref artist) = image.metadata.artist {
                 push_alt!("illustrator", artist);
             }
         }
@@ -256,13 +274,14 @@ impl DataFile {
         }
         // rarity
         if mse_game != MseGame::Vanguard {
-            push_alt!("rarity", match card.rarity() {
-                Rarity::Land => "basic land",
-                Rarity::Common => "common",
-                Rarity::Uncommon => "uncommon",
-                Rarity::Rare => "rare",
-                Rarity::Mythic => "mythic rare",
-                Rarity::Special => "special"
+            push_alt!("rarity", match (card.rarity(), art_handler.printing_rarity(card)) {
+                (Rarity::Land, _) => "basic land",
+                (_, Some(printing_rarity)) => printing_rarity,
+                (Rarity::Common, None) => "common",
+                (Rarity::Uncommon, None) => "uncommon",
+                (Rarity::Rare, None) => "rare",
+                (Rarity::Mythic, None) => "mythic rare",
+                (Rarity::Special, None) => "special"
             });
         }
         // text
@@ -357,7 +376,7 @@ impl DataFile {
         }
         // stylesheet
         if !alt {
-            let stylesheet = match mse_game {
+            let generated_stylesheet = match mse_game {
                 MseGame::Magic => match card.layout() {
                     Layout::Normal => {
                         if card.type_line() >= CardType::Plane || card.type_line() >= CardType::Phenomenon {
@@ -387,6 +406,8 @@ impl DataFile {
                 MseGame::Archenemy => "standard",
                 MseGame::Vanguard => "standard"
             };
+            let stylesheet = overrides.and_then(|overrides| overrides.fields.get("stylesheet")).map_or(generated_stylesheet, |stylesheet| &stylesheet[..]);
+            art_handler.set_stylesheet(card, stylesheet);
             if stylesheet != if mse_game == MseGame::Magic { "m15-altered" } else { "standard" } {
                 result.push("stylesheet", stylesheet);
             }
@@ -394,39 +415,39 @@ impl DataFile {
             match stylesheet {
                 "m15-altered" => {
                     if card.type_line() >= CardType::Enchantment && card.type_line().types().iter().filter(|&&card_type| card_type != CardType::Tribal).count() >= 2 {
-                        result.push_styling(args, stylesheet, "frames", "nyx");
+                        result.push_generated_styling(args, stylesheet, "frames", "nyx");
                     }
                     if card.color_indicator().is_some() {
-                        result.push_styling(args, stylesheet, "color indicator dot", "yes");
+                        result.push_generated_styling(args, stylesheet, "color indicator dot", "yes");
                     }
                 }
                 "m15-mainframe-dfc" => {
                     let back = match card.layout() {
                         Layout::DoubleFaced { back, .. } |
-                        Layout::Meld { back, .. } => back,
-                        layout => { panic!("unexpected layout for m15-mainframe-dfc: {:?}", layout); }
+                        Layout::Meld { back, .. } => Some(back),
+                        _ => None // stylesheet was overridden for a card without a back face
                     };
                     if card.type_line() >= CardType::Planeswalker {
                         let num_text_boxes = match separated_text_boxes {
                             Some(boxes) => boxes.len(),
                             None => 3 //TODO verbose warning
                         };
-                        result.push_styling(args, stylesheet, "front style", format!("{} ability planeswalker", num_text_boxes));
+                        result.push_generated_styling(args, stylesheet, "front style", format!("{} ability planeswalker", num_text_boxes));
                     }
-                    if back.type_line() >= CardType::Planeswalker {
+                    if back.map_or(false, |back| back.type_line() >= CardType::Planeswalker) {
                         let num_text_boxes = 3; //TODO
-                        result.push_styling(args, stylesheet, "back style", format!("{} ability planeswalker", num_text_boxes));
+                        result.push_generated_styling(args, stylesheet, "back style", format!("{} ability planeswalker", num_text_boxes));
                     }
                 }
                 "m15-mainframe-planeswalker" => {
                     if card.color_indicator().is_some() {
-                        result.push_styling(args, stylesheet, "color indicator dot", "yes");
+                        result.push_generated_styling(args, stylesheet, "color indicator dot", "yes");
                     }
                     let num_text_boxes = match separated_text_boxes {
                         Some(boxes) => boxes.len(),
                         None => 3 //TODO verbose warning
                     };
-                    result.push_styling(args, stylesheet, "use separate textboxes", match num_text_boxes {
+                    result.push_generated_styling(args, stylesheet, "use separate textboxes", match num_text_boxes {
                         2 => "two",
                         3 => "three",
                         4 => "four",
@@ -435,6 +456,22 @@ impl DataFile {
                 }
                 _ => {}
             }
+            if let Some(overrides) = overrides {
+                for (key, value) in &overrides.styling_data {
+                    result.push_styling(args, stylesheet, key, value);
+                }
+            }
+        }
+        // overrides
+        if let Some(overrides) = overrides {
+            for (key, value) in &overrides.fields {
+                if key == "stylesheet" { continue; } // handled above
+                if alt {
+                    result.set(format!("{} 2", key), value);
+                } else {
+                    result.set(key, value);
+                }
+            }
         }
         result
     }
@@ -463,7 +500,22 @@ impl DataFile {
         }
         match &mut self["styling data"] {
             Data::Flat(text) => { panic!("found flat styling data: {:?}", text); }
-            Data::Subfile(f) => { f.push(key, value); }
+            Data::Subfile(f) => { f.set(key, value); }
+        }
+    }
+
+    /// Per-card values generated by MSG don't replace options given using `--styling`.
+    fn push_generated_styling(&mut self, args: &ArgsRegular, stylesheet: &str, key: &str, value: impl Into<Data>) {
+        if args.styling.get(stylesheet).map_or(false, |options| options.contains_key(key)) { return; }
+        self.push_styling(args, stylesheet, key, value);
+    }
+
+    fn set(&mut self, key: impl ToString, value: impl Into<Data>) {
+        let key = key.to_string();
+        if let Some((_, v)) = self.items.iter_mut().find(|(k, _)| *k == key) {
+            *v = value.into();
+        } else {
+            self.items.push((key, value.into()));
         }
     }
 
@@ -496,10 +548,18 @@ impl DataFile {
         Ok(())
     }
 
-    pub fn write_to(self, buf: impl Write + Seek, art_handler: &mut ArtHandler) -> Result<(), Error> {
+    pub fn write_to(mut self, buf: impl Write + Seek, art_handler: &mut ArtHandler) -> Result<(), Error> {
+        let num_cards = self.items.iter().filter(|(key, _)| key == "card").count();
+        if let Data::Flat(description) = &mut self["set info"].expect_subfile_mut("found flat set info")["description"] {
+            *description = description.replace("{count}", &num_cards.to_string());
+        }
         let mut zip = ZipWriter::new(buf);
         zip.start_file("set", FileOptions::default())?;
         self.write_inner(&mut zip, 0).at_unknown()?;
+        if let Some(mut symbol) = art_handler.open_set_symbol()? {
+            zip.start_file(SET_SYMBOL_FILENAME, FileOptions::default())?;
+            io::copy(&mut symbol, &mut zip).at_unknown()?;
+        }
         for result in art_handler.open_images() {
             let (i, mut image) = result?;
             zip.start_file(format!("image{}", i), FileOptions::default())?;
@@ -641,8 +701,9 @@ fn cost_to_mse(cost: ManaCost) -> String {
     }).collect()
 }
 
+/// Also used as the base for per-card styling data. The defaults generated here can be changed using `--styling`.
 fn set_styling_data(args: &ArgsRegular, stylesheet: &str) -> DataFile {
-    match stylesheet {
+    let mut styling_data = match stylesheet {
         "m15-altered" => DataFile::from_iter(vec![
             ("other options", Data::from("brown legendary vehicle pt, ancestral generic mana")),
             ("use holofoil stamps", Data::from(if args.holofoil_stamps { "yes" } else { "no" })),
@@ -657,7 +718,13 @@ fn set_styling_data(args: &ArgsRegular, stylesheet: &str) -> DataFile {
             ("holofoil stamped rares", Data::from(if args.holofoil_stamps { "yes" } else { "no" }))
         ]),
         _ => DataFile::default()
+    };
+    if let Some(options) = args.styling.get(stylesheet) {
+        for (key, value) in options {
+            styling_data.set(key, value);
+        }
     }
+    styling_data
 }
 
 fn symbols_to_mse(text: &str) -> String {
@@ -0,0 +1,342 @@
+//! Matching user-entered card names against the card database.
+
+use {
+    std::{
+        collections::HashMap,
+        fmt,
+        fs::{
+            self,
+            File
+        },
+        io::BufReader,
+        path::{
+            Path,
+            PathBuf
+        }
+    },
+    deunicode::deunicode,
+    gitdir::Host as _,
+    itertools::Itertools as _,
+    mtg::card::{
+        Card,
+        Db
+    },
+    serde::{
+        Deserialize,
+        de::{
+            DeserializeSeed,
+            Deserializer,
+            IgnoredAny,
+            MapAccess,
+            SeqAccess,
+            Visitor
+        }
+    },
+    reqwest::blocking::Client,
+    strsim::damerau_levenshtein,
+    crate::{
+        args::ArgsRegular,
+        scryfall,
+        util::{
+            Error,
+            IoResultExt as _,
+            card_faces
+        }
+    }
+};
+
+const MAX_SUGGESTIONS: usize = 5;
+
+pub(crate) struct NameIndex {
+    /// Also contains full names of multi-part cards and foreign printed names.
+    aliases: HashMap<String, String>,
+    /// Normalized face name, face name, database name.
+    names: Vec<(String, String, String)>
+}
+
+impl NameIndex {
+    /// `mtg_json` must be the MTG JSON data the database was loaded from, since foreign names are matched to database names by their English names.
+    pub(crate) fn new(db: &Db, mtg_json: Option<&Path>) -> Result<NameIndex, Error> {
+        let mut index = NameIndex::from_faces(db.clone().into_iter().map(|card| (
+            card_faces(&card).into_iter().map(|face| face.to_string()).collect(),
+            card.primary().to_string()
+        )));
+        if let Some(mtg_json) = mtg_json {
+            for (foreign_name, english_name) in foreign_names(mtg_json)? {
+                if let Some(db_name) = index.aliases.get(&normalize(&english_name)).cloned() {
+                    index.aliases.entry(normalize(&foreign_name)).or_insert(db_name);
+                }
+            }
+        }
+        Ok(index)
+    }
+
+    /// Takes the face names and the database name of each card.
+    fn from_faces(cards: impl IntoIterator<Item = (Vec<String>, String)>) -> NameIndex {
+        let mut aliases = HashMap::default();
+        let mut names = Vec::default();
+        for (faces, db_name) in cards {
+            aliases.entry(normalize(&faces.join(" // "))).or_insert_with(|| db_name.clone());
+            for face_name in faces {
+                let normalized = normalize(&face_name);
+                aliases.entry(normalized.clone()).or_insert_with(|| db_name.clone());
+                names.push((normalized, face_name, db_name.clone()));
+            }
+        }
+        names.sort();
+        names.dedup();
+        NameIndex { aliases, names }
+    }
+
+    pub(crate) fn resolve(&self, name: &str) -> Option<&str> {
+        self.aliases.get(&normalize(name)).map(String::as_str)
+    }
+
+    /// Only used if there is no local MTG JSON data, which `new` would have read the foreign names from.
+    pub(crate) fn resolve_online(&self, client: &Client, args: &ArgsRegular, name: &str) -> Option<&str> {
+        if args.offline || mtg_json_path(args).is_some() { return None; }
+        let english_name = scryfall::english_name(client, &args.scryfall_api_url, name).ok()??; //TODO print error if in verbose mode
+        self.resolve(&english_name)
+    }
+
+    /// Only corrects the name if a single face name is closest and within the threshold, otherwise returns suggestions.
+    pub(crate) fn fuzzy_match(&self, name: &str) -> Result<(&str, &str), Vec<String>> {
+        let normalized = normalize(name);
+        let threshold = (normalized.chars().count() / 5).max(1).min(3);
+        let mut candidates = self.names.iter()
+            .map(|(normalized_name, face_name, db_name)| (damerau_levenshtein(&normalized, normalized_name), face_name, db_name))
+            .filter(|&(distance, _, _)| distance <= 2 * threshold)
+            .collect::<Vec<_>>();
+        candidates.sort();
+        match candidates.first() {
+            Some(&(best_distance, face_name, db_name)) if best_distance <= threshold && candidates.get(1).map_or(true, |&(distance, _, _)| distance > best_distance) => Ok((face_name.as_str(), db_name.as_str())),
+            _ => Err(candidates.into_iter().take(MAX_SUGGESTIONS).map(|(_, face_name, _)| face_name.clone()).collect())
+        }
+    }
+}
+
+/// Unlike card generation, this skips card names which aren't found instead of correcting them.
+pub(crate) fn lookup_cards(client: &Client, db: &Db, args: &ArgsRegular, card_names: impl IntoIterator<Item = String>) -> Result<Vec<Card>, Error> {
+    let mut name_index = None;
+    let mut cards = Vec::default();
+    for card_name in card_names {
+        if let Some(card) = db.card(&card_name) {
+            cards.push(card);
+        } else {
+            if name_index.is_none() {
+                name_index = Some(NameIndex::new(db, mtg_json_path(args).as_deref())?);
+            }
+            let name_index = name_index.as_ref().expect("name index was just created");
+            if let Some(card) = name_index.resolve(&card_name).or_else(|| name_index.resolve_online(client, args, &card_name)).and_then(|db_name| db.card(db_name)) {
+                cards.push(card);
+            }
+        }
+    }
+    Ok(cards)
+}
+
+pub(crate) fn mtg_json_path(args: &ArgsRegular) -> Option<PathBuf> {
+    if let Some(ref db_path) = args.database {
+        Some(db_path.clone())
+    } else if args.offline {
+        gitdir::GitHub.repo("fenhl/lore-seeker").master().ok().map(|repo_path| repo_path.join("data").join("sets"))
+    } else {
+        None
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MtgJsonCard {
+    pub(crate) name: Option<String>,
+    pub(crate) face_name: Option<String>,
+    pub(crate) number: Option<String>,
+    pub(crate) artist: Option<String>,
+    pub(crate) printings: Option<Vec<String>>,
+    pub(crate) foreign_data: Option<Vec<ForeignData>>
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ForeignData {
+    pub(crate) name: Option<String>
+}
+
+fn foreign_names(path: &Path) -> Result<Vec<(String, String)>, Error> {
+    let mut names = Vec::default();
+    for_each_mtg_json_card(path, |_, card| {
+        let english_name = if let Some(english_name) = card.name { english_name } else { return };
+        for foreign_name in card.foreign_data.into_iter().flatten().filter_map(|foreign_data| foreign_data.name) {
+            // split cards have foreign names like “Feuer // Eis”, so also add the individual halves
+            let english_parts = english_name.split(" // ").collect::<Vec<_>>();
+            let foreign_parts = foreign_name.split(" // ").collect::<Vec<_>>();
+            if english_parts.len() > 1 && english_parts.len() == foreign_parts.len() {
+                names.extend(foreign_parts.into_iter().zip(english_parts).map(|(foreign_part, english_part)| (foreign_part.to_owned(), english_part.to_owned())));
+            }
+            names.push((foreign_name, english_name.clone()));
+        }
+    })?;
+    Ok(names)
+}
+
+/// The files are read as a stream, since AllSets files are too large to be kept in memory.
+pub(crate) fn for_each_mtg_json_card(path: &Path, mut f: impl FnMut(&str, MtgJsonCard)) -> Result<(), Error> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path).at(path)? {
+            let entry_path = entry.at(path)?.path();
+            if entry_path.extension().map_or(false, |ext| ext == "json") {
+                visit_mtg_json_file(&entry_path, &mut f)?;
+            }
+        }
+    } else {
+        visit_mtg_json_file(path, &mut f)?;
+    }
+    Ok(())
+}
+
+fn visit_mtg_json_file(path: &Path, f: &mut impl FnMut(&str, MtgJsonCard)) -> Result<(), Error> {
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(File::open(path).at(path)?));
+    SetsVisitor(f).deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(())
+}
+
+/// Finds all objects with a `cards` array, since the nesting of sets differs between MTG JSON versions.
+struct SetsVisitor<'a, F: FnMut(&str, MtgJsonCard)>(&'a mut F);
+
+impl<'a, 'de, F: FnMut(&str, MtgJsonCard)> DeserializeSeed<'de> for SetsVisitor<'a, F> {
+    type Value = Option<String>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<String>, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'a, 'de, F: FnMut(&str, MtgJsonCard)> Visitor<'de> for SetsVisitor<'a, F> {
+    type Value = Option<String>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("MTG JSON data")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Option<String>, E> { Ok(None) }
+    fn visit_i64<E>(self, _: i64) -> Result<Option<String>, E> { Ok(None) }
+    fn visit_u64<E>(self, _: u64) -> Result<Option<String>, E> { Ok(None) }
+    fn visit_f64<E>(self, _: f64) -> Result<Option<String>, E> { Ok(None) }
+    fn visit_unit<E>(self) -> Result<Option<String>, E> { Ok(None) }
+    fn visit_str<E>(self, value: &str) -> Result<Option<String>, E> { Ok(Some(value.to_owned())) }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Option<String>, A::Error> {
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(None)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Option<String>, A::Error> {
+        let SetsVisitor(f) = self;
+        let mut set_code = None;
+        let mut cards = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "cards" {
+                cards = map.next_value::<CardList>()?.0;
+            } else {
+                let value = map.next_value_seed(SetsVisitor(&mut *f))?;
+                if key == "code" { set_code = value }
+            }
+        }
+        for card in cards.into_iter().flatten() {
+            f(set_code.as_deref().unwrap_or_default(), card);
+        }
+        Ok(None)
+    }
+}
+
+/// Booster sheets have a `cards` object rather than a list, so it's `None` for those.
+struct CardList(Option<Vec<MtgJsonCard>>);
+
+impl<'de> Deserialize<'de> for CardList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<CardList, D::Error> {
+        deserializer.deserialize_any(CardListVisitor)
+    }
+}
+
+struct CardListVisitor;
+
+impl<'de> Visitor<'de> for CardListVisitor {
+    type Value = CardList;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a list of cards or an object")
+    }
+
+    fn visit_unit<E>(self) -> Result<CardList, E> { Ok(CardList(None)) }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<CardList, A::Error> {
+        let mut cards = Vec::default();
+        while let Some(card) = seq.next_element()? {
+            cards.push(card);
+        }
+        Ok(CardList(Some(cards)))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<CardList, A::Error> {
+        while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+        Ok(CardList(None))
+    }
+}
+
+/// Ignores case, diacritics, ligatures like `Æ`, and punctuation.
+pub(crate) fn normalize(name: &str) -> String {
+    deunicode(name)
+        .to_lowercase()
+        .chars()
+        .map(|c| if c == '-' || c == '/' { ' ' } else { c })
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(cards: &[&[&str]]) -> NameIndex {
+        NameIndex::from_faces(cards.iter().map(|faces| (faces.iter().map(|&face| face.to_owned()).collect(), faces[0].to_owned())))
+    }
+
+    #[test]
+    fn normalize_ignores_case_diacritics_and_punctuation() {
+        assert_eq!(normalize("Æther Vial"), "aether vial");
+        assert_eq!(normalize("Lim-Dûl's Vault"), "lim duls vault");
+        assert_eq!(normalize("Fire // Ice"), "fire ice");
+    }
+
+    #[test]
+    fn resolve_matches_faces_and_full_names() {
+        let index = index(&[&["Fire", "Ice"], &["Æther Vial"]]);
+        assert_eq!(index.resolve("ice"), Some("Fire"));
+        assert_eq!(index.resolve("Fire // Ice"), Some("Fire"));
+        assert_eq!(index.resolve("aether vial"), Some("Æther Vial"));
+        assert_eq!(index.resolve("Aether"), None);
+    }
+
+    #[test]
+    fn fuzzy_match_corrects_within_threshold() {
+        let index = index(&[&["Lightning Bolt"], &["Lightning Helix"], &["Æther Vial"]]);
+        assert_eq!(index.fuzzy_match("Lightnig Bolt"), Ok(("Lightning Bolt", "Lightning Bolt")));
+        assert_eq!(index.fuzzy_match("Aether Vail"), Ok(("Æther Vial", "Æther Vial")));
+    }
+
+    #[test]
+    fn fuzzy_match_suggests_beyond_threshold() {
+        let index = index(&[&["Opt"]]);
+        assert_eq!(index.fuzzy_match("Ogt"), Ok(("Opt", "Opt")));
+        assert_eq!(index.fuzzy_match("Oxx"), Err(vec![format!("Opt")]));
+        assert_eq!(index.fuzzy_match("Ugh"), Err(Vec::default()));
+    }
+
+    #[test]
+    fn fuzzy_match_refuses_ties() {
+        let index = index(&[&["Bolt"], &["Boat"]]);
+        assert_eq!(index.fuzzy_match("Boit"), Err(vec![format!("Boat"), format!("Bolt")]));
+    }
+}
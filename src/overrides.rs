@@ -0,0 +1,78 @@
+//! Per-card overrides for the generated MSE data, see `--overrides`.
+
+use {
+    std::{
+        collections::BTreeMap,
+        path::{
+            Path,
+            PathBuf
+        }
+    },
+    mtg::card::Card,
+    serde::{
+        Deserialize,
+        Deserializer
+    },
+    crate::{
+        names,
+        util::{
+            Error,
+            full_card_name,
+            load_card_map
+        }
+    }
+};
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct CardOverrides {
+    /// Relative to the overrides file.
+    pub image: Option<PathBuf>,
+    /// Replaces generated values as well as `--styling`.
+    #[serde(default, rename = "styling data", deserialize_with = "scalar_map")]
+    pub styling_data: BTreeMap<String, String>,
+    #[serde(flatten, deserialize_with = "scalar_map")]
+    pub fields: BTreeMap<String, String>
+}
+
+/// MSE data is text-only, so numbers and booleans are converted to strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool)
+}
+
+impl From<Scalar> for String {
+    fn from(scalar: Scalar) -> String {
+        match scalar {
+            Scalar::String(s) => s,
+            Scalar::Integer(n) => n.to_string(),
+            Scalar::Float(x) => x.to_string(),
+            Scalar::Boolean(b) => b.to_string()
+        }
+    }
+}
+
+fn scalar_map<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error> {
+    Ok(BTreeMap::<String, Scalar>::deserialize(deserializer)?.into_iter().map(|(key, value)| (key, value.into())).collect())
+}
+
+/// Keyed by normalized card name.
+#[derive(Debug, Default, Clone)]
+pub struct Overrides(BTreeMap<String, CardOverrides>);
+
+impl Overrides {
+    pub(crate) fn load(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.0.extend(load_card_map(path.as_ref(), |card_overrides: &mut CardOverrides, base_dir| {
+            card_overrides.image = card_overrides.image.take().map(|img_path| base_dir.join(img_path));
+        })?);
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, card: &Card) -> Option<&CardOverrides> {
+        self.0.get(&names::normalize(&card.to_string()))
+            .or_else(|| self.0.get(&names::normalize(&full_card_name(card))))
+    }
+}
@@ -0,0 +1,547 @@
+//! Resolving `=` queries to card names, using the backend selected with `--query-backend`.
+//!
+//! This also contains a local search engine, supporting a subset of [Lore Seeker syntax](https://lore-seeker.cards/help/syntax).
+//! Supported are card names (bare or quoted words, or `!` for exact names), `t:` (type line), `o:` (rules text), `c:` (colors), `cmc:` (mana value), `r:` (rarity), and `e:` (set), combined with implicit “and”, `or`, `-` for negation, and parentheses.
+
+use {
+    std::{
+        cmp::Ordering,
+        collections::{
+            HashMap,
+            HashSet
+        },
+        iter::Peekable,
+        path::{
+            Path,
+            PathBuf
+        },
+        process::Command,
+        vec
+    },
+    gitdir::Host as _,
+    lazy_static::lazy_static,
+    mtg::{
+        card::{
+            Card,
+            Db,
+            Layout,
+            Rarity
+        },
+        cost::ManaSymbol
+    },
+    regex::Regex,
+    reqwest::blocking::Client,
+    crate::{
+        args::{
+            ArgsRegular,
+            QueryBackend
+        },
+        names,
+        scryfall,
+        util::{
+            CommandOutputExt as _,
+            Error,
+            card_faces
+        }
+    }
+};
+
+/// In the order of the bits used for color sets.
+const COLORS: [char; 5] = ['w', 'u', 'b', 'r', 'g'];
+
+/// Keyed by lowercase face name.
+type SetCodes = HashMap<String, HashSet<String>>;
+
+lazy_static! {
+    static ref FILTER_REGEX: Regex = Regex::new("^([A-Za-z]+)(:|>=|<=|!=|=|<|>)(.+)$").expect("failed to build query filter regex");
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Not,
+    Or,
+    Word(String)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge
+}
+
+impl Comparison {
+    fn parse(op: &str) -> Comparison {
+        match op {
+            ":" | "=" => Comparison::Eq,
+            "!=" => Comparison::Ne,
+            "<" => Comparison::Lt,
+            "<=" => Comparison::Le,
+            ">" => Comparison::Gt,
+            ">=" => Comparison::Ge,
+            _ => unreachable!("filter regex matched unknown operator {}", op)
+        }
+    }
+
+    fn holds(&self, ordering: Option<Ordering>) -> bool {
+        match (self, ordering) {
+            (_, None) => *self == Comparison::Ne,
+            (Comparison::Eq, Some(ordering)) => ordering == Ordering::Equal,
+            (Comparison::Ne, Some(ordering)) => ordering != Ordering::Equal,
+            (Comparison::Lt, Some(ordering)) => ordering == Ordering::Less,
+            (Comparison::Le, Some(ordering)) => ordering != Ordering::Greater,
+            (Comparison::Gt, Some(ordering)) => ordering == Ordering::Greater,
+            (Comparison::Ge, Some(ordering)) => ordering != Ordering::Less
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Filter {
+    Name(String),
+    ExactName(String),
+    Type(String),
+    Oracle(String),
+    /// A subset of the colors is “less” and a superset is “greater”.
+    Colors(Comparison, u8),
+    Multicolored,
+    Cmc(Comparison, u64),
+    Rarity(Comparison, u8),
+    /// Uppercase set code.
+    Set(String)
+}
+
+impl Filter {
+    fn parse(word: &str) -> Result<Filter, Error> {
+        if word.starts_with('!') {
+            return Ok(Filter::ExactName(word[1..].to_lowercase()));
+        }
+        let captures = if let Some(captures) = FILTER_REGEX.captures(word) { captures } else { return Ok(Filter::Name(word.to_lowercase())); };
+        let value = captures[3].to_lowercase();
+        let comparison = Comparison::parse(&captures[2]);
+        match &captures[1].to_lowercase()[..] {
+            "t" | "type" => Ok(Filter::Type(value)),
+            "o" | "oracle" => Ok(Filter::Oracle(value)),
+            "c" | "color" => match &value[..] {
+                "m" | "multicolor" => Ok(Filter::Multicolored),
+                "c" | "colorless" => Ok(Filter::Colors(comparison, 0)),
+                _ => {
+                    let mut colors = 0;
+                    for letter in value.chars() {
+                        colors |= color_bit(letter).ok_or_else(|| Error::Query(format!("unknown color: {}", letter)))?;
+                    }
+                    // like on Lore Seeker, c: finds cards with at least the given colors
+                    Ok(Filter::Colors(if &captures[2] == ":" { Comparison::Ge } else { comparison }, colors))
+                }
+            },
+            "cmc" | "mv" => Ok(Filter::Cmc(comparison, value.parse().map_err(|_| Error::Query(format!("invalid mana value: {}", value)))?)),
+            "r" | "rarity" => Ok(Filter::Rarity(comparison, match &value[..] {
+                "b" | "basic" | "c" | "common" => 0,
+                "u" | "uncommon" => 1,
+                "r" | "rare" => 2,
+                "m" | "mythic" => 3,
+                "s" | "special" => 4,
+                _ => { return Err(Error::Query(format!("unknown rarity: {}", value))); }
+            })),
+            "e" | "s" | "set" | "edition" => if comparison == Comparison::Eq {
+                Ok(Filter::Set(value.to_uppercase()))
+            } else {
+                Err(Error::Query(format!("set filters only support the operators : and =")))
+            },
+            key => Err(Error::Query(format!("unknown filter: {}", key)))
+        }
+    }
+
+    fn matches(&self, card: &Card, set_codes: &SetCodes) -> bool {
+        match self {
+            // also match the full name of multi-part cards
+            Filter::Name(name) => if card_faces(card).into_iter().map(|face| face.to_string().to_lowercase()).collect::<Vec<_>>().join(" // ").contains(name) { return true; },
+            // the mana value is a property of the whole card, not of the individual faces
+            Filter::Cmc(comparison, cmc) => { return comparison.holds(Some(mana_value(card).cmp(cmc))); }
+            // so are the colors of split cards
+            Filter::Colors(comparison, colors) => if let Some(card_colors) = split_colors(card) { return colors_match(*comparison, card_colors, *colors); },
+            Filter::Multicolored => if let Some(card_colors) = split_colors(card) { return card_colors.count_ones() >= 2; },
+            _ => {}
+        }
+        card_faces(card).into_iter().any(|face| match self {
+            Filter::Name(name) => face.to_string().to_lowercase().contains(name),
+            Filter::ExactName(name) => face.to_string().to_lowercase() == *name,
+            Filter::Type(type_line) => face.type_line().to_string().to_lowercase().contains(type_line),
+            Filter::Oracle(text) => face.abilities().into_iter().any(|ability| ability.to_string().to_lowercase().contains(text)),
+            Filter::Colors(comparison, colors) => colors_match(*comparison, colors_of(&face), *colors),
+            Filter::Multicolored => colors_of(&face).count_ones() >= 2,
+            Filter::Cmc(_, _) => unreachable!("mana value filter is checked for the whole card"),
+            Filter::Rarity(comparison, rarity) => comparison.holds(Some(match face.rarity() {
+                Rarity::Land | Rarity::Common => 0,
+                Rarity::Uncommon => 1,
+                Rarity::Rare => 2,
+                Rarity::Mythic => 3,
+                Rarity::Special => 4
+            }.cmp(rarity))),
+            Filter::Set(set_code) => set_codes.get(&face.to_string().to_lowercase()).map_or(false, |face_set_codes| face_set_codes.contains(set_code))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Query {
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    Filter(Filter)
+}
+
+impl Query {
+    fn parse(query: &str) -> Result<Query, Error> {
+        let mut tokens = tokenize(query)?.into_iter().peekable();
+        let parsed = Query::parse_or(&mut tokens)?;
+        if tokens.next().is_some() { return Err(Error::Query(format!("unmatched closing parenthesis"))); }
+        Ok(parsed)
+    }
+
+    fn parse_or(tokens: &mut Peekable<vec::IntoIter<Token>>) -> Result<Query, Error> {
+        let mut alternatives = vec![Query::parse_and(tokens)?];
+        while tokens.peek() == Some(&Token::Or) {
+            tokens.next();
+            alternatives.push(Query::parse_and(tokens)?);
+        }
+        Ok(if alternatives.len() == 1 { alternatives.remove(0) } else { Query::Or(alternatives) })
+    }
+
+    fn parse_and(tokens: &mut Peekable<vec::IntoIter<Token>>) -> Result<Query, Error> {
+        let mut conjuncts = Vec::default();
+        loop {
+            match tokens.peek() {
+                None | Some(Token::Close) | Some(Token::Or) => break,
+                Some(_) => { conjuncts.push(Query::parse_unary(tokens)?); }
+            }
+        }
+        if conjuncts.is_empty() { return Err(Error::Query(format!("empty query or subquery"))); }
+        Ok(if conjuncts.len() == 1 { conjuncts.remove(0) } else { Query::And(conjuncts) })
+    }
+
+    fn parse_unary(tokens: &mut Peekable<vec::IntoIter<Token>>) -> Result<Query, Error> {
+        match tokens.next() {
+            Some(Token::Not) => Ok(Query::Not(Box::new(Query::parse_unary(tokens)?))),
+            Some(Token::Open) => {
+                let inner = Query::parse_or(tokens)?;
+                if tokens.next() != Some(Token::Close) { return Err(Error::Query(format!("missing closing parenthesis"))); }
+                Ok(inner)
+            }
+            Some(Token::Word(word)) => Ok(Query::Filter(Filter::parse(&word)?)),
+            Some(Token::Close) | Some(Token::Or) | None => Err(Error::Query(format!("missing search term after -")))
+        }
+    }
+
+    fn matches(&self, card: &Card, set_codes: &SetCodes) -> bool {
+        match self {
+            Query::And(conjuncts) => conjuncts.iter().all(|query| query.matches(card, set_codes)),
+            Query::Or(alternatives) => alternatives.iter().any(|query| query.matches(card, set_codes)),
+            Query::Not(query) => !query.matches(card, set_codes),
+            Query::Filter(filter) => filter.matches(card, set_codes)
+        }
+    }
+
+    /// Set filters require the MTG JSON data.
+    fn has_set_filter(&self) -> bool {
+        match self {
+            Query::And(queries) | Query::Or(queries) => queries.iter().any(Query::has_set_filter),
+            Query::Not(query) => query.has_set_filter(),
+            Query::Filter(filter) => matches!(filter, Filter::Set(_))
+        }
+    }
+}
+
+pub(crate) fn card_names(client: &Client, args: &ArgsRegular, db: &Db, query: &str) -> Result<Vec<String>, Error> {
+    let backend = args.query_backend.unwrap_or_else(|| if args.find_cards.is_some() {
+        QueryBackend::FindCards
+    } else if args.offline {
+        if default_find_cards().is_some() { QueryBackend::FindCards } else { QueryBackend::Local }
+    } else {
+        QueryBackend::LoreSeeker
+    });
+    match backend {
+        QueryBackend::FindCards => if let Some(exe_path) = args.find_cards.clone().or_else(default_find_cards) {
+            find_cards(&exe_path, query)
+        } else {
+            Err(Error::Args(format!("the find-cards query backend requires --find-cards or a local copy of the Lore Seeker repository")))
+        },
+        QueryBackend::Local => resolve(db, names::mtg_json_path(args).as_deref(), query),
+        QueryBackend::LoreSeeker => if args.offline {
+            Err(Error::Args(format!("the lore-seeker query backend can't be used in offline mode")))
+        } else {
+            Ok(lore_seeker::resolve_query(args.lore_seeker_hostname.as_deref(), query)?.1.into_iter().map(|(card_name, _)| card_name).collect()) //TODO async
+        },
+        QueryBackend::Scryfall => if args.offline {
+            Err(Error::Args(format!("the scryfall query backend can't be used in offline mode")))
+        } else {
+            scryfall::search(client, &args.scryfall_api_url, query)
+        }
+    }
+}
+
+fn default_find_cards() -> Option<PathBuf> {
+    let exe_path = gitdir::GitHub.repo("fenhl/lore-seeker").master().ok()?.join("search-engine").join("bin").join("find_cards");
+    if exe_path.exists() { Some(exe_path) } else { None }
+}
+
+/// `mtg_json` is only used for set filters.
+fn resolve(db: &Db, mtg_json: Option<&Path>, query: &str) -> Result<Vec<String>, Error> {
+    let query = Query::parse(query)?;
+    let set_codes = if query.has_set_filter() {
+        set_codes(mtg_json.ok_or_else(|| Error::Query(format!("set filters require a local card database, see --db")))?)?
+    } else {
+        SetCodes::default()
+    };
+    Ok(db.clone().into_iter()
+        .filter(|card| query.matches(card, &set_codes))
+        .map(|card| card.to_string())
+        .collect())
+}
+
+fn set_codes(mtg_json: &Path) -> Result<SetCodes, Error> {
+    let mut set_codes = SetCodes::default();
+    names::for_each_mtg_json_card(mtg_json, |set_code, card| {
+        let printings = card.printings.iter().flatten()
+            .map(String::as_str)
+            .chain(if set_code.is_empty() { None } else { Some(set_code) })
+            .map(str::to_uppercase)
+            .collect::<HashSet<_>>();
+        // depending on the MTG JSON version, parts of multi-part cards are named like “Fire // Ice” or “Fire”
+        let face_names = card.name.iter()
+            .flat_map(|name| name.split(" // "))
+            .chain(card.face_name.as_deref());
+        for name in face_names {
+            set_codes.entry(name.to_lowercase()).or_default().extend(printings.iter().cloned());
+        }
+    })?;
+    Ok(set_codes)
+}
+
+/// The executable must be compatible with the one from [magic-search-engine](https://github.com/taw/magic-search-engine).
+pub(crate) fn find_cards(exe_path: &Path, query: &str) -> Result<Vec<String>, Error> {
+    let output = Command::new(exe_path).arg(query).check("find_cards")?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::default();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '(' => { chars.next(); tokens.push(Token::Open); }
+            ')' => { chars.next(); tokens.push(Token::Close); }
+            '-' => { chars.next(); tokens.push(Token::Not); }
+            c if c.is_whitespace() => { chars.next(); }
+            _ => {
+                let mut word = String::default();
+                let mut quoted = false;
+                let mut has_quotes = false;
+                while let Some(&c) = chars.peek() {
+                    if c == '"' {
+                        quoted = !quoted;
+                        has_quotes = true;
+                    } else if !quoted && (c.is_whitespace() || c == '(' || c == ')') {
+                        break;
+                    } else {
+                        word.push(c);
+                    }
+                    chars.next();
+                }
+                if quoted { return Err(Error::Query(format!("unmatched quotation mark"))); }
+                tokens.push(if !has_quotes && word.eq_ignore_ascii_case("or") { Token::Or } else { Token::Word(word) });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn color_bit(letter: char) -> Option<u8> {
+    COLORS.iter().position(|&color| color == letter).map(|idx| 1 << idx)
+}
+
+fn colors_match(comparison: Comparison, card_colors: u8, colors: u8) -> bool {
+    comparison.holds(if card_colors == colors {
+        Some(Ordering::Equal)
+    } else if card_colors & colors == card_colors {
+        Some(Ordering::Less)
+    } else if card_colors & colors == colors {
+        Some(Ordering::Greater)
+    } else {
+        None
+    })
+}
+
+/// `None` for cards other than split cards, whose faces have their own colors.
+fn split_colors(card: &Card) -> Option<u8> {
+    if let Layout::Split { .. } = card.primary().layout() {
+        Some(card_faces(&card.primary()).iter().fold(0, |colors, face| colors | colors_of(face)))
+    } else {
+        None
+    }
+}
+
+fn colors_of(card: &Card) -> u8 {
+    let mut colors = 0;
+    if let Some(mana_cost) = card.mana_cost() {
+        for symbol in mana_cost.symbols() {
+            colors |= match symbol {
+                ManaSymbol::Variable | ManaSymbol::Generic(_) | ManaSymbol::Snow | ManaSymbol::Runic | ManaSymbol::Colorless => 0,
+                ManaSymbol::TwobridWhite | ManaSymbol::PhyrexianWhite | ManaSymbol::White => 0b00001,
+                ManaSymbol::TwobridBlue | ManaSymbol::PhyrexianBlue | ManaSymbol::Blue => 0b00010,
+                ManaSymbol::TwobridBlack | ManaSymbol::PhyrexianBlack | ManaSymbol::Black => 0b00100,
+                ManaSymbol::TwobridRed | ManaSymbol::PhyrexianRed | ManaSymbol::Red => 0b01000,
+                ManaSymbol::TwobridGreen | ManaSymbol::PhyrexianGreen | ManaSymbol::Green => 0b10000,
+                ManaSymbol::HybridWhiteBlue => 0b00011,
+                ManaSymbol::HybridBlueBlack => 0b00110,
+                ManaSymbol::HybridBlackRed => 0b01100,
+                ManaSymbol::HybridRedGreen => 0b11000,
+                ManaSymbol::HybridGreenWhite => 0b10001,
+                ManaSymbol::HybridWhiteBlack => 0b00101,
+                ManaSymbol::HybridBlueRed => 0b01010,
+                ManaSymbol::HybridBlackGreen => 0b10100,
+                ManaSymbol::HybridRedWhite => 0b01001,
+                ManaSymbol::HybridGreenBlue => 0b10010
+            };
+        }
+    }
+    if let Some(indicator) = card.color_indicator() {
+        for color in indicator.canonical_order() {
+            colors |= match &color.to_string().to_lowercase()[..] {
+                "white" => 0b00001,
+                "blue" => 0b00010,
+                "black" => 0b00100,
+                "red" => 0b01000,
+                "green" => 0b10000,
+                _ => 0
+            };
+        }
+    }
+    colors
+}
+
+/// The total of both halves for split cards, and that of the front face for other multi-part cards.
+fn mana_value(card: &Card) -> u64 {
+    if let Layout::Split { .. } = card.primary().layout() {
+        card_faces(&card.primary()).iter().map(cmc_of).sum()
+    } else {
+        cmc_of(&card.primary())
+    }
+}
+
+fn cmc_of(card: &Card) -> u64 {
+    card.mana_cost().map_or(0, |mana_cost| mana_cost.symbols().into_iter().map(|symbol| match symbol {
+        ManaSymbol::Variable => 0,
+        ManaSymbol::Generic(n) => n as u64,
+        ManaSymbol::TwobridWhite | ManaSymbol::TwobridBlue | ManaSymbol::TwobridBlack | ManaSymbol::TwobridRed | ManaSymbol::TwobridGreen => 2,
+        _ => 1
+    }).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(word: &str) -> Token { Token::Word(word.into()) }
+    fn name(name: &str) -> Query { Query::Filter(Filter::Name(name.into())) }
+
+    #[test]
+    fn tokenize_operators() {
+        assert_eq!(tokenize("t:creature -(c:r OR c:g)").unwrap(), vec![word("t:creature"), Token::Not, Token::Open, word("c:r"), Token::Or, word("c:g"), Token::Close]);
+    }
+
+    #[test]
+    fn tokenize_quoted_words() {
+        assert_eq!(tokenize(r#"o:"draw a card" "or" !"Fire // Ice""#).unwrap(), vec![word("o:draw a card"), word("or"), word("!Fire // Ice")]);
+    }
+
+    #[test]
+    fn tokenize_dash_inside_word() {
+        assert_eq!(tokenize("half-elf").unwrap(), vec![word("half-elf")]);
+    }
+
+    #[test]
+    fn tokenize_unmatched_quote() {
+        assert!(matches!(tokenize(r#"o:"draw"#), Err(Error::Query(_))));
+    }
+
+    #[test]
+    fn parse_and_binds_tighter_than_or() {
+        assert_eq!(Query::parse("a b or c").unwrap(), Query::Or(vec![Query::And(vec![name("a"), name("b")]), name("c")]));
+    }
+
+    #[test]
+    fn parse_not() {
+        assert_eq!(Query::parse("-a b").unwrap(), Query::And(vec![Query::Not(Box::new(name("a"))), name("b")]));
+        assert_eq!(Query::parse("--a").unwrap(), Query::Not(Box::new(Query::Not(Box::new(name("a"))))));
+    }
+
+    #[test]
+    fn parse_parentheses() {
+        assert_eq!(Query::parse("a (b or c)").unwrap(), Query::And(vec![name("a"), Query::Or(vec![name("b"), name("c")])]));
+        assert_eq!(Query::parse("-(a or b)").unwrap(), Query::Not(Box::new(Query::Or(vec![name("a"), name("b")]))));
+    }
+
+    #[test]
+    fn parse_quoted_words() {
+        assert_eq!(Query::parse(r#""lightning bolt" or !"Fire // Ice""#).unwrap(), Query::Or(vec![name("lightning bolt"), Query::Filter(Filter::ExactName("fire // ice".into()))]));
+    }
+
+    #[test]
+    fn parse_errors() {
+        for query in &["", "a or", "or a", "(a", "a)", "()", "-", "a -", r#"o:"draw"#, "foo:bar"] {
+            assert!(matches!(Query::parse(query), Err(Error::Query(_))), "query {:?} should be rejected", query);
+        }
+    }
+
+    #[test]
+    fn filter_names() {
+        assert_eq!(Filter::parse("Bolt").unwrap(), Filter::Name("bolt".into()));
+        assert_eq!(Filter::parse("!Lightning Bolt").unwrap(), Filter::ExactName("lightning bolt".into()));
+        assert_eq!(Filter::parse("t:Creature").unwrap(), Filter::Type("creature".into()));
+        assert_eq!(Filter::parse("o:Flying").unwrap(), Filter::Oracle("flying".into()));
+    }
+
+    #[test]
+    fn filter_colors() {
+        assert_eq!(Filter::parse("c:rg").unwrap(), Filter::Colors(Comparison::Ge, 0b11000));
+        assert_eq!(Filter::parse("c=rg").unwrap(), Filter::Colors(Comparison::Eq, 0b11000));
+        assert_eq!(Filter::parse("c<=wu").unwrap(), Filter::Colors(Comparison::Le, 0b00011));
+        assert_eq!(Filter::parse("c:c").unwrap(), Filter::Colors(Comparison::Eq, 0));
+        assert_eq!(Filter::parse("c!=colorless").unwrap(), Filter::Colors(Comparison::Ne, 0));
+        assert_eq!(Filter::parse("c:m").unwrap(), Filter::Multicolored);
+        assert!(matches!(Filter::parse("c:x"), Err(Error::Query(_))));
+    }
+
+    #[test]
+    fn filter_mana_value_and_rarity() {
+        assert_eq!(Filter::parse("cmc>=3").unwrap(), Filter::Cmc(Comparison::Ge, 3));
+        assert_eq!(Filter::parse("mv<2").unwrap(), Filter::Cmc(Comparison::Lt, 2));
+        assert!(matches!(Filter::parse("mv:x"), Err(Error::Query(_))));
+        assert_eq!(Filter::parse("r:mythic").unwrap(), Filter::Rarity(Comparison::Eq, 3));
+        assert_eq!(Filter::parse("r>u").unwrap(), Filter::Rarity(Comparison::Gt, 1));
+        assert!(matches!(Filter::parse("r:foo"), Err(Error::Query(_))));
+    }
+
+    #[test]
+    fn filter_sets() {
+        assert_eq!(Filter::parse("e:m10").unwrap(), Filter::Set("M10".into()));
+        assert_eq!(Filter::parse("set=mh2").unwrap(), Filter::Set("MH2".into()));
+        assert!(matches!(Filter::parse("e>m10"), Err(Error::Query(_))));
+    }
+
+    #[test]
+    fn filter_unknown() {
+        assert!(matches!(Filter::parse("foo:bar"), Err(Error::Query(_))));
+    }
+}
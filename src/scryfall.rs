@@ -0,0 +1,104 @@
+//! Access to the [Scryfall API](https://scryfall.com/docs/api), used for card images, printings, and `=` queries with `--query-backend=scryfall`.
+
+use {
+    std::{
+        thread,
+        time::{
+            Duration,
+            Instant
+        }
+    },
+    lazy_static::lazy_static,
+    parking_lot::Mutex,
+    reqwest::{
+        StatusCode,
+        blocking::{
+            Client,
+            Response
+        }
+    },
+    serde::Deserialize,
+    url::Url,
+    crate::{
+        names,
+        util::Error
+    }
+};
+
+/// Scryfall asks for 50–100 milliseconds between requests, see <https://scryfall.com/docs/api#rate-limits-and-good-citizenship>.
+const RATE_LIMIT: Duration = Duration::from_millis(100);
+
+lazy_static! {
+    static ref NEXT_REQUEST_TIME: Mutex<Option<Instant>> = Mutex::default();
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchPage {
+    data: Vec<SearchResult>,
+    has_more: bool,
+    next_page: Option<Url>
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResult {
+    name: String,
+    printed_name: Option<String>,
+    #[serde(default)]
+    card_faces: Vec<SearchResultFace>
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResultFace {
+    name: String,
+    printed_name: Option<String>
+}
+
+/// Waits as necessary to respect the rate limit.
+pub(crate) fn request(client: &Client, url: &Url) -> Result<Response, reqwest::Error> {
+    let mut next_request_time = NEXT_REQUEST_TIME.lock();
+    if let Some(next_request_time) = *next_request_time {
+        let now = Instant::now();
+        if next_request_time > now {
+            thread::sleep(next_request_time - now);
+        }
+    }
+    let result = client.get(url.as_str()).send();
+    *next_request_time = Some(Instant::now() + RATE_LIMIT);
+    result
+}
+
+pub(crate) fn search(client: &Client, api_url: &Url, query: &str) -> Result<Vec<String>, Error> {
+    let mut url = api_url.join("cards/search")?;
+    url.query_pairs_mut().append_pair("q", query).append_pair("unique", "cards");
+    let mut card_names = Vec::default();
+    loop {
+        let resp = request(client, &url)?;
+        if resp.status() == StatusCode::NOT_FOUND { break; } // Scryfall responds with 404 if there are no results
+        let page = resp.error_for_status()?.json::<SearchPage>()?;
+        card_names.extend(page.data.into_iter().map(|card| card.name));
+        match page.next_page {
+            Some(next_page) if page.has_more => { url = next_page; }
+            _ => break
+        }
+    }
+    Ok(card_names)
+}
+
+/// Also matches the printed names of individual card faces.
+pub(crate) fn english_name(client: &Client, api_url: &Url, printed_name: &str) -> Result<Option<String>, Error> {
+    let mut url = api_url.join("cards/search")?;
+    url.query_pairs_mut()
+        .append_pair("q", &format!("\"{}\" lang:any", printed_name.replace('"', "")))
+        .append_pair("include_multilingual", "true")
+        .append_pair("unique", "prints");
+    let resp = request(client, &url)?;
+    if resp.status() == StatusCode::NOT_FOUND { return Ok(None); }
+    let page = resp.error_for_status()?.json::<SearchPage>()?;
+    let normalized = names::normalize(printed_name);
+    let matches = |printed_name: &Option<String>| printed_name.as_ref().map_or(false, |printed_name| names::normalize(printed_name) == normalized);
+    Ok(page.data.into_iter().find_map(|card| if matches(&card.printed_name) {
+        Some(card.name)
+    } else {
+        card.card_faces.into_iter().find(|face| matches(&face.printed_name)).map(|face| face.name)
+    }))
+}
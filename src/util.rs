@@ -1,7 +1,9 @@
 use {
     std::{
+        collections::BTreeMap,
         convert::Infallible,
         fmt,
+        fs,
         io,
         path::{
             Path,
@@ -13,7 +15,14 @@ use {
         }
     },
     derive_more::From,
-    mtg::card::DbError
+    itertools::Itertools as _,
+    mtg::card::{
+        Card,
+        DbError,
+        Layout
+    },
+    serde::de::DeserializeOwned,
+    crate::names
 };
 #[cfg(windows)] use std::os::windows::process::CommandExt as _;
 
@@ -65,6 +74,40 @@ impl StrExt for str {
     }
 }
 
+pub(crate) fn card_faces(card: &Card) -> Vec<Card> {
+    let mut faces = vec![card.clone()];
+    match card.layout() {
+        Layout::Normal => {}
+        Layout::Split { right: alt_part, .. } |
+        Layout::Flip { flipped: alt_part, .. } |
+        Layout::DoubleFaced { back: alt_part, .. } |
+        Layout::Meld { back: alt_part, .. } |
+        Layout::Adventure { adventure: alt_part, .. } => { faces.push(alt_part); }
+    }
+    faces
+}
+
+/// The names of all faces of the card, joined with ` // ` like on Scryfall, e.g. `Fire // Ice`.
+pub(crate) fn full_card_name(card: &Card) -> String {
+    card_faces(&card.primary()).into_iter().map(|face| face.to_string()).join(" // ")
+}
+
+/// Keys are normalized (see `names::normalize`), so that entries match cards regardless of case, diacritics, and punctuation.
+/// `resolve_paths` is called with the directory containing the file, so relative paths in the entries can be resolved against it.
+pub(crate) fn load_card_map<T: DeserializeOwned>(path: &Path, resolve_paths: impl Fn(&mut T, &Path)) -> Result<BTreeMap<String, T>, Error> {
+    let text = fs::read_to_string(path).at(path)?;
+    let entries = if path.extension().map_or(false, |ext| ext == "toml") {
+        toml::from_str::<BTreeMap<String, T>>(&text)?
+    } else {
+        serde_json::from_str::<BTreeMap<String, T>>(&text)?
+    };
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    Ok(entries.into_iter().map(|(card_name, mut entry)| {
+        resolve_paths(&mut entry, base_dir);
+        (names::normalize(&card_name), entry)
+    }).collect())
+}
+
 #[derive(Debug, From)]
 pub enum Error {
     #[from(ignore)]
@@ -74,12 +117,13 @@ pub enum Error {
     #[from(ignore)]
     CardGen(String, String),
     #[from(ignore)]
-    CardNotFound(String),
+    CardNotFound(String, Vec<String>),
     ColorParse(css_color_parser::ColorParseError),
     #[from(ignore)]
     CommandExit(&'static str, Output),
     Db(DbError),
     GitDir(gitdir::host::github::Error),
+    Image(image::ImageError),
     InvalidHeaderValue(reqwest::header::InvalidHeaderValue),
     #[from(ignore)]
     Io(io::Error, Option<PathBuf>),
@@ -90,11 +134,17 @@ pub enum Error {
     MissingHomeDir,
     MissingPackage,
     MissingRelease,
+    #[from(ignore)]
+    Query(String),
     Reqwest(reqwest::Error),
     SameVersion,
     SemVer(semver::SemVerError),
+    Toml(toml::de::Error),
+    TomlSer(toml::ser::Error),
+    UrlParse(url::ParseError),
     VersionCommand,
     VersionRegression,
+    Xml(xml::reader::Error),
     Zip(zip::result::ZipError)
 }
 
@@ -110,11 +160,16 @@ impl fmt::Display for Error {
             Error::Annotated(msg, e) => write!(f, "{}: {}", msg, e),
             Error::Args(msg) => msg.fmt(f),
             Error::CardGen(card_name, msg) => write!(f, "error generating {}: {}", card_name, msg),
-            Error::CardNotFound(card_name) => write!(f, "no card named {:?} found", card_name),
+            Error::CardNotFound(card_name, suggestions) => if suggestions.is_empty() {
+                write!(f, "no card named {:?} found", card_name)
+            } else {
+                write!(f, "no card named {:?} found, did you mean {}?", card_name, suggestions.iter().map(|suggestion| format!("{:?}", suggestion)).join(" or "))
+            },
             Error::ColorParse(e) => e.fmt(f),
             Error::CommandExit(cmd, ref output) => write!(f, "subprocess {} exited with status {}", cmd, output.status),
             Error::Db(e) => write!(f, "card database error: {:?}", e), //TODO impl Display for DbError
             Error::GitDir(e) => write!(f, "gitdir error: {:?}", e), //TODO impl Display for gitdir Error
+            Error::Image(e) => write!(f, "image processing error: {}", e),
             Error::InvalidHeaderValue(e) => e.fmt(f),
             Error::Io(e, Some(path)) => write!(f, "I/O error at {}: {}", path.display(), e),
             Error::Io(e, None) => write!(f, "I/O error: {}", e),
@@ -125,6 +180,7 @@ impl fmt::Display for Error {
             Error::MissingHomeDir => write!(f, "Could not find your user folder."),
             Error::MissingPackage => write!(f, "The binary to be released was not found in Cargo.toml"),
             Error::MissingRelease => write!(f, "The program does not appear to be installed via `cargo install`, but no releases were found on the GitHub repo."),
+            Error::Query(msg) => write!(f, "invalid query: {}", msg),
             Error::Reqwest(e) => if let Some(url) = e.url() {
                 write!(f, "error downloading {}: {}", url, e)
             } else {
@@ -132,8 +188,12 @@ impl fmt::Display for Error {
             },
             Error::SameVersion => write!(f, "The release being created has the same version as the latest release."),
             Error::SemVer(e) => e.fmt(f),
+            Error::Toml(e) => e.fmt(f),
+            Error::TomlSer(e) => e.fmt(f),
+            Error::UrlParse(e) => e.fmt(f),
             Error::VersionCommand => write!(f, "Could not check version of the installed update."),
             Error::VersionRegression => write!(f, "The release being created has a lower version than the latest release."),
+            Error::Xml(e) => e.fmt(f),
             Error::Zip(e) => e.fmt(f)
         }
     }